@@ -0,0 +1,54 @@
+extern crate chrono;
+#[macro_use]
+extern crate criterion;
+extern crate cronlist;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use criterion::{black_box, Criterion};
+use cronlist::crontab::Recurrence;
+
+const SCHEDULES: [(&str, [&str; 5]); 4] = [
+    ("every minute", ["*", "*", "*", "*", "*"]),
+    ("weekday mornings", ["30", "6", "*", "*", "1-5"]),
+    ("first week of the month", ["0", "9", "1-7", "*", "*"]),
+    ("leap day", ["0", "0", "29", "2", "*"]),
+];
+
+fn start() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2001, 3, 1)
+        .unwrap()
+        .and_hms_opt(12, 34, 56)
+        .unwrap()
+}
+
+/// The cost of finding a single run.
+fn next_match(criterion: &mut Criterion) {
+    for &(name, ref fields) in &SCHEDULES {
+        let recurrence = Recurrence::new(fields).unwrap();
+        criterion.bench_function(&format!("next_match {}", name), |bencher| {
+            bencher.iter(|| recurrence.next_match(black_box(start())))
+        });
+    }
+}
+
+/// The cost of listing a year of runs of an every minute job, as when listing many hosts.
+fn year_of_minutes(criterion: &mut Criterion) {
+    let recurrence = Recurrence::new(&["*", "*", "*", "*", "*"]).unwrap();
+    let end = start() + Duration::days(365);
+    criterion.bench_function("year of every minute", |bencher| {
+        bencher.iter(|| {
+            let mut current = black_box(start());
+            while current < end {
+                current = recurrence.next_match(current).unwrap();
+            }
+            current
+        })
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = next_match, year_of_minutes
+}
+criterion_main!(benches);
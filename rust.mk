@@ -15,6 +15,10 @@ release:
 test:
 	cargo test
 
+.PHONY: bench
+bench:
+	cargo bench
+
 .PHONY: lint
 lint:
 	find . -name '*.rs' -exec rustfmt {} +
@@ -0,0 +1,9 @@
+/// Which crontab format to expect.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CrontabKind {
+    /// A personal crontab, as edited with `crontab -e`, where every entry runs as the crontab's owner.
+    #[default]
+    User,
+    /// `/etc/crontab` or a file in `/etc/cron.d`, where the user to run as follows the time fields.
+    System,
+}
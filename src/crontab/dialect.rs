@@ -0,0 +1,67 @@
+use std::str::FromStr;
+
+/// Which cron implementation's rules to follow.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Dialect {
+    /// Vixie cron and its descendants, such as cronie: when both the day of month and day of week fields are
+    /// restricted, a day matching *either* of them matches. A field starting with `*` (including `*/2`) counts
+    /// as unrestricted, just like in Vixie cron.
+    #[default]
+    Vixie,
+    /// Implementations which always require both the day of month and day of week to match.
+    Conjunctive,
+    /// Spring's `@Scheduled` expressions, which start with a seconds field.
+    Spring,
+    /// Quartz expressions, which start with a seconds field, end with a year field and count the days of the week
+    /// from 1 for Sunday.
+    Quartz,
+}
+
+impl Dialect {
+    /// Whether schedules start with a seconds field.
+    pub fn has_seconds(self) -> bool {
+        self == Dialect::Spring || self == Dialect::Quartz
+    }
+
+    /// Whether schedules end with a year field.
+    pub fn has_year(self) -> bool {
+        self == Dialect::Quartz
+    }
+
+    /// The number of time fields in a schedule.
+    pub fn field_count(self) -> usize {
+        5 + usize::from(self.has_seconds()) + usize::from(self.has_year())
+    }
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Dialect, String> {
+        match name {
+            "vixie" => Ok(Dialect::Vixie),
+            "conjunctive" => Ok(Dialect::Conjunctive),
+            "spring" => Ok(Dialect::Spring),
+            "quartz" => Ok(Dialect::Quartz),
+            _ => Err(format!("unknown dialect ‘{}’", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Dialect;
+
+    #[test]
+    fn should_count_fields_of_each_dialect() {
+        assert_eq!(Dialect::Vixie.field_count(), 5);
+        assert_eq!(Dialect::Spring.field_count(), 6);
+        assert_eq!(Dialect::Quartz.field_count(), 7);
+    }
+
+    #[test]
+    fn should_parse_dialect_name() {
+        assert_eq!("quartz".parse(), Ok(Dialect::Quartz));
+        assert!("fcron".parse::<Dialect>().is_err());
+    }
+}
@@ -0,0 +1,508 @@
+use crontab::{Crontab, EditError, Entry, Environment, ParseError, ParseOptions};
+use std::borrow::Cow;
+use std::fmt;
+use std::mem;
+
+/// A crontab as written, keeping every line with its exact text so it can be written back unchanged.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Document<'a> {
+    lines: Vec<Line<'a>>,
+}
+
+/// A line of a crontab.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line<'a> {
+    /// The text of the line, without its line ending.
+    pub text: Cow<'a, str>,
+    /// `"\n"`, `"\r\n"`, or empty for a last line without a line ending.
+    pub ending: &'a str,
+    kind: LineKind,
+}
+
+/// What a line of a crontab holds, the way cron reads it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineKind {
+    /// Empty or only whitespace.
+    Blank,
+    /// Starts with `#`, possibly after whitespace.
+    Comment,
+    /// A `NAME=value` environment variable assignment.
+    Assignment,
+    /// Anything else, whether or not it parses.
+    Entry,
+}
+
+/// Where to insert a line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Anchor<'b> {
+    /// After the line with this number, or at the start for zero.
+    Line(usize),
+    /// After the first comment which reads exactly this, ignoring surrounding whitespace.
+    Marker(&'b str),
+}
+
+impl LineKind {
+    pub fn of(text: &str) -> LineKind {
+        let trimmed = text.trim_start();
+        if trimmed.is_empty() {
+            LineKind::Blank
+        } else if trimmed.starts_with('#') {
+            LineKind::Comment
+        } else if Environment::parse_assignment(text).is_some() {
+            LineKind::Assignment
+        } else {
+            LineKind::Entry
+        }
+    }
+}
+
+impl<'a> Document<'a> {
+    /// Splits `input` into lines. Nothing is lost, so writing the document back gives `input` byte for byte.
+    pub fn parse(input: &'a str) -> Document<'a> {
+        let lines = input
+            .split_inclusive('\n')
+            .map(|line| {
+                let text = line
+                    .strip_suffix("\r\n")
+                    .or_else(|| line.strip_suffix('\n'))
+                    .unwrap_or(line);
+                Line::new(Cow::Borrowed(text), &line[text.len()..])
+            })
+            .collect();
+        Document { lines }
+    }
+
+    /// Every line, in file order.
+    pub fn lines(&self) -> &[Line<'a>] {
+        &self.lines
+    }
+
+    /// Parses each entry with the environment and zone assigned before it, like `Crontab::parse`, numbering lines from
+    /// one. Assignments of unknown zones are errors too.
+    pub fn entries<'b>(&'b self, options: &ParseOptions) -> impl Iterator<Item = Result<Entry<'b>, ParseError>> + 'b {
+        let lines = self.lines.iter().map(|line| (&line.text[..], line.kind));
+        Crontab::parse_lines(lines, options).0.into_iter()
+    }
+
+    /// Inserts `entry` as a new line after `anchor`, returning its line number.
+    pub fn insert_after(&mut self, anchor: Anchor, entry: &str, options: &ParseOptions) -> Result<usize, EditError> {
+        let after = match anchor {
+            Anchor::Line(0) => 0,
+            Anchor::Line(line) => self.line(line).map(|_| line)?,
+            Anchor::Marker(marker) => self
+                .lines
+                .iter()
+                .position(|line| line.kind() == LineKind::Comment && line.text.trim() == marker.trim())
+                .map(|index| index + 1)
+                .ok_or_else(|| EditError::NoSuchMarker(marker.to_string()))?,
+        };
+        self.validate(entry, after + 1, options)?;
+
+        // The new line takes over the ending of the one before it, so a missing final line ending stays missing
+        let ending = self.ending();
+        let ending = match after {
+            0 => ending,
+            _ => mem::replace(&mut self.lines[after - 1].ending, ending),
+        };
+        self.lines
+            .insert(after, Line::new(Cow::Owned(entry.to_string()), ending));
+        Ok(after + 1)
+    }
+
+    /// Deletes every entry for which `predicate` is true, returning how many were deleted. Lines which don't parse
+    /// are kept.
+    pub fn remove_entries<F>(&mut self, options: &ParseOptions, mut predicate: F) -> usize
+    where
+        F: FnMut(&Entry) -> bool,
+    {
+        let removed: Vec<usize> = self
+            .entries(options)
+            .filter_map(Result::ok)
+            .filter(|entry| predicate(entry))
+            .map(|entry| entry.line)
+            .collect();
+        let mut line = 0;
+        self.lines.retain(|_| {
+            line += 1;
+            !removed.contains(&line)
+        });
+        removed.len()
+    }
+
+    /// Comments out the entry on `line`.
+    pub fn disable(&mut self, line: usize) -> Result<(), EditError> {
+        let current = self.line(line)?;
+        if current.kind() != LineKind::Entry {
+            return Err(EditError::NotAnEntry(line));
+        }
+        let text = format!("#{}", current.text);
+        self.lines[line - 1].set_text(text);
+        Ok(())
+    }
+
+    /// Removes the first `#` from the commented out entry on `line`, undoing `disable`.
+    pub fn enable(&mut self, line: usize, options: &ParseOptions) -> Result<(), EditError> {
+        let current = self.line(line)?;
+        if current.kind() != LineKind::Comment {
+            return Err(EditError::NotADisabledEntry(line));
+        }
+        let start = current.text.len() - current.text.trim_start().len();
+        let text = format!("{}{}", &current.text[..start], &current.text[start + 1..]);
+        if LineKind::of(&text) != LineKind::Entry {
+            return Err(EditError::NotADisabledEntry(line));
+        }
+        self.validate(&text, line, options)?;
+        self.lines[line - 1].set_text(text);
+        Ok(())
+    }
+
+    /// Replaces the schedule of the entry on `line`, keeping the rest of the line as it is.
+    pub fn set_schedule(&mut self, line: usize, schedule: &str, options: &ParseOptions) -> Result<(), EditError> {
+        let schedule = schedule.trim();
+        let text = {
+            let (text, entry) = self.entry(line, options)?;
+            let start = offset(text, entry.schedule);
+            format!(
+                "{}{}{}",
+                &text[..start],
+                schedule,
+                &text[start + entry.schedule.len()..]
+            )
+        };
+        if self.validate(&text, line, options)?.schedule != schedule {
+            return Err(EditError::NotASchedule(schedule.to_string()));
+        }
+        self.lines[line - 1].set_text(text);
+        Ok(())
+    }
+
+    /// Replaces the command of the entry on `line`, keeping the rest of the line as it is.
+    pub fn set_command(&mut self, line: usize, command: &str, options: &ParseOptions) -> Result<(), EditError> {
+        let text = {
+            let (text, entry) = self.entry(line, options)?;
+            format!("{}{}", &text[..offset(text, entry.raw_command)], command.trim_start())
+        };
+        self.validate(&text, line, options)?;
+        self.lines[line - 1].set_text(text);
+        Ok(())
+    }
+
+    fn line(&self, line: usize) -> Result<&Line<'a>, EditError> {
+        line.checked_sub(1)
+            .and_then(|index| self.lines.get(index))
+            .ok_or(EditError::NoSuchLine(line))
+    }
+
+    /// The text of `line` and the entry parsed from it.
+    fn entry<'b>(&'b self, line: usize, options: &ParseOptions) -> Result<(&'b str, Entry<'b>), EditError> {
+        let current = self.line(line)?;
+        let entry = self.validate(&current.text, line, options)?;
+        Ok((&current.text, entry))
+    }
+
+    /// The line ending used in the document, `"\n"` if there's none yet.
+    fn ending(&self) -> &'a str {
+        self.lines
+            .iter()
+            .map(|line| line.ending)
+            .find(|ending| !ending.is_empty())
+            .unwrap_or("\n")
+    }
+
+    /// Parses `text` as the entry it would be on `line`, with the environment and zone assigned by the lines before it,
+    /// like `entries`.
+    fn validate<'b>(&'b self, text: &'b str, line: usize, options: &ParseOptions) -> Result<Entry<'b>, EditError> {
+        if text.contains(['\n', '\r']) {
+            return Err(EditError::LineBreak);
+        }
+        if LineKind::of(text) != LineKind::Entry {
+            return Err(EditError::NotAnEntry(line));
+        }
+        let lines = self.lines[..line - 1]
+            .iter()
+            .map(|line| (&line.text[..], line.kind))
+            .chain(Some((text, LineKind::Entry)));
+        // The line is an entry, so the last result is its own
+        let (mut results, _) = Crontab::parse_lines(lines, options);
+        results
+            .pop()
+            .expect("the last line is an entry")
+            .map_err(EditError::Invalid)
+    }
+}
+
+/// The byte offset of `part` within `text`, which it must be a slice of.
+fn offset(text: &str, part: &str) -> usize {
+    part.as_ptr() as usize - text.as_ptr() as usize
+}
+
+impl<'a> Line<'a> {
+    fn new(text: Cow<'a, str>, ending: &'a str) -> Line<'a> {
+        let kind = LineKind::of(&text);
+        Line { text, ending, kind }
+    }
+
+    pub fn kind(&self) -> LineKind {
+        self.kind
+    }
+
+    fn set_text(&mut self, text: String) {
+        self.kind = LineKind::of(&text);
+        self.text = Cow::Owned(text);
+    }
+}
+
+impl<'a> fmt::Display for Document<'a> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.lines {
+            write!(formatter, "{}{}", line.text, line.ending)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Anchor, Document, LineKind};
+    use chrono_tz::Pacific::Auckland;
+    use crontab::{EditError, ParseError, ParseOptions, Position};
+    use test_support::user_options;
+
+    const CRONTAB: &str = "# m h dom mon dow command\r\n\
+                           MAILTO = \"ops@example.com\"  \n\
+                           \t\n\
+                           */15  9-17 * * MON-FRI\tcheck --quiet   # not a comment\n\
+                           \n\
+                           61 * * * * broken\n\
+                           @reboot start";
+
+    #[test]
+    fn should_write_unmodified_document_back_byte_for_byte() {
+        for input in &[CRONTAB, "", "\n", "\r\n\r\n", "1 2 3 4 5 command\n", "  # indented\n\n"] {
+            assert_eq!(Document::parse(input).to_string(), *input);
+        }
+    }
+
+    #[test]
+    fn should_keep_line_text_and_ending_apart() {
+        let document = Document::parse(CRONTAB);
+        let lines = document.lines();
+        assert_eq!(lines.len(), 7);
+        assert_eq!(lines[0].text, "# m h dom mon dow command");
+        assert_eq!(lines[0].ending, "\r\n");
+        assert_eq!(lines[3].text, "*/15  9-17 * * MON-FRI\tcheck --quiet   # not a comment");
+        assert_eq!(lines[6].ending, "");
+    }
+
+    #[test]
+    fn should_classify_lines() {
+        let kinds: Vec<LineKind> = Document::parse(CRONTAB)
+            .lines()
+            .iter()
+            .map(|line| line.kind())
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                LineKind::Comment,
+                LineKind::Assignment,
+                LineKind::Blank,
+                LineKind::Entry,
+                LineKind::Blank,
+                LineKind::Entry,
+                LineKind::Entry,
+            ]
+        );
+    }
+
+    #[test]
+    fn should_parse_entries_with_line_numbers() {
+        let document = Document::parse(CRONTAB);
+        let options = ParseOptions::default();
+        let entries: Vec<_> = document.entries(&options).collect();
+        assert_eq!(entries.len(), 3);
+        let entry = entries[0].as_ref().unwrap();
+        assert_eq!(entry.command, "check --quiet   # not a comment");
+        assert_eq!(entry.line, 4);
+        assert_eq!(
+            entries[1].as_ref().err(),
+            Some(&ParseError::OutOfRange {
+                value: 61,
+                min: 0,
+                max: 59,
+                position: Position { line: 6, span: 0..2 },
+            })
+        );
+        assert_eq!(entries[2].as_ref().unwrap().line, 7);
+    }
+
+    #[test]
+    fn should_parse_entries_with_environment_assigned_before_them() {
+        let document = Document::parse(
+            "0 0 * * * first
+CRON_TZ=Pacific/Auckland
+PATH=/bin
+0 0 * * * second
+",
+        );
+        let options = user_options();
+        let entries: Vec<_> = document.entries(&options).map(Result::unwrap).collect();
+        assert_eq!(entries[0].timezone, None);
+        assert_eq!(entries[0].environment.get("PATH"), None);
+        assert_eq!(entries[1].timezone, Some(Auckland));
+        assert_eq!(entries[1].environment.get("PATH"), Some("/bin"));
+        assert_eq!(entries[1].user, "alice");
+    }
+
+    #[test]
+    fn should_report_unknown_zone_among_entries() {
+        let document = Document::parse(
+            "TZ=Nowhere
+0 0 * * * command
+",
+        );
+        let entries: Vec<_> = document.entries(&ParseOptions::default()).collect();
+        assert_eq!(
+            entries[0].as_ref().err(),
+            Some(&ParseError::UnknownTimeZone {
+                name: "Nowhere".to_string(),
+                position: Position { line: 1, span: 3..10 },
+            })
+        );
+        assert_eq!(entries[1].as_ref().map(|entry| entry.timezone).ok(), Some(None));
+    }
+
+    #[test]
+    fn should_insert_entry_after_line_or_marker() {
+        let options = ParseOptions::default();
+        let mut document = Document::parse("# Backups\r\n0 1 * * * backup");
+        assert_eq!(
+            document.insert_after(Anchor::Line(2), "0 2 * * * verify", &options),
+            Ok(3)
+        );
+        assert_eq!(
+            document.insert_after(Anchor::Marker("# Backups"), "0 0 * * * prepare", &options),
+            Ok(2)
+        );
+        assert_eq!(
+            document.insert_after(Anchor::Line(0), "MAILTO=ops", &options),
+            Err(EditError::NotAnEntry(1))
+        );
+        assert_eq!(
+            document.to_string(),
+            "# Backups\r\n0 0 * * * prepare\r\n0 1 * * * backup\r\n0 2 * * * verify"
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_insertion() {
+        let options = ParseOptions::default();
+        let mut document = Document::parse("1 2 3 4 5 command\n");
+        assert_eq!(
+            document.insert_after(Anchor::Line(1), "1 24 * * * command", &options),
+            Err(EditError::Invalid(ParseError::OutOfRange {
+                value: 24,
+                min: 0,
+                max: 23,
+                position: Position { line: 2, span: 2..4 },
+            }))
+        );
+        assert_eq!(
+            document.insert_after(Anchor::Marker("# Missing"), "* * * * * command", &options),
+            Err(EditError::NoSuchMarker("# Missing".to_string()))
+        );
+        assert_eq!(
+            document.insert_after(Anchor::Line(2), "* * * * * command", &options),
+            Err(EditError::NoSuchLine(2))
+        );
+        assert_eq!(
+            document.insert_after(Anchor::Line(1), "* * * * * first\n* * * * * second", &options),
+            Err(EditError::LineBreak)
+        );
+        assert_eq!(document.to_string(), "1 2 3 4 5 command\n");
+    }
+
+    #[test]
+    fn should_remove_matching_entries() {
+        let options = ParseOptions::default();
+        let mut document = Document::parse(CRONTAB);
+        assert_eq!(document.remove_entries(&options, |entry| entry.command != "start"), 1);
+        assert_eq!(document.lines().len(), 6);
+        assert_eq!(document.lines()[3].text, "");
+        assert_eq!(document.lines()[4].text, "61 * * * * broken");
+    }
+
+    #[test]
+    fn should_remove_entries_seeing_environment_assigned_before_them() {
+        let options = ParseOptions::default();
+        let mut document = Document::parse(
+            "0 0 * * * first
+CRON_TZ=Pacific/Auckland
+0 0 * * * second
+",
+        );
+        assert_eq!(
+            document.remove_entries(&options, |entry| entry.timezone == Some(Auckland)),
+            1
+        );
+        assert_eq!(document.to_string(), "0 0 * * * first\nCRON_TZ=Pacific/Auckland\n");
+    }
+
+    #[test]
+    fn should_disable_and_enable_entry() {
+        let options = ParseOptions::default();
+        let mut document = Document::parse(CRONTAB);
+        assert_eq!(document.disable(4), Ok(()));
+        assert_eq!(
+            document.lines()[3].text,
+            "#*/15  9-17 * * MON-FRI\tcheck --quiet   # not a comment"
+        );
+        assert_eq!(document.lines()[3].kind(), LineKind::Comment);
+        assert_eq!(document.enable(4, &options), Ok(()));
+        assert_eq!(document.lines()[3].kind(), LineKind::Entry);
+        assert_eq!(document.to_string(), CRONTAB);
+
+        assert_eq!(document.disable(1), Err(EditError::NotAnEntry(1)));
+        assert_eq!(document.enable(3, &options), Err(EditError::NotADisabledEntry(3)));
+        assert_eq!(
+            document.enable(1, &options).map_err(|error| error.to_string()),
+            Err("line 1, column 6: invalid number ‘dom’".to_string())
+        );
+    }
+
+    #[test]
+    fn should_set_schedule_keeping_rest_of_line() {
+        let options = ParseOptions::default();
+        let mut document = Document::parse(CRONTAB);
+        assert_eq!(document.set_schedule(4, "@hourly", &options), Ok(()));
+        assert_eq!(document.lines()[3].text, "@hourly\tcheck --quiet   # not a comment");
+        assert_eq!(
+            document.set_schedule(4, "0 * * * * *", &options),
+            Err(EditError::NotASchedule("0 * * * * *".to_string()))
+        );
+        assert_eq!(
+            document
+                .set_schedule(6, "0 * * * *", &options)
+                .map_err(|error| error.to_string()),
+            Err("line 6, column 1: value 61 is outside the range 0-59".to_string())
+        );
+        assert_eq!(
+            document.set_schedule(5, "0 * * * *", &options),
+            Err(EditError::NotAnEntry(5))
+        );
+    }
+
+    #[test]
+    fn should_set_command_keeping_schedule() {
+        let options = ParseOptions::default();
+        let mut document = Document::parse(CRONTAB);
+        assert_eq!(document.set_command(7, "start --verbose", &options), Ok(()));
+        assert_eq!(document.lines()[6].text, "@reboot start --verbose");
+        assert_eq!(
+            document
+                .set_command(7, " ", &options)
+                .map_err(|error| error.to_string()),
+            Err("line 7, column 8: missing command".to_string())
+        );
+    }
+}
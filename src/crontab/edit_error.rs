@@ -0,0 +1,32 @@
+use crontab::ParseError;
+use std::error::Error;
+use std::fmt;
+
+/// Why an edit of a crontab document was rejected. Line numbers count from one.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EditError {
+    NoSuchLine(usize),
+    NoSuchMarker(String),
+    NotAnEntry(usize),
+    NotADisabledEntry(usize),
+    NotASchedule(String),
+    LineBreak,
+    /// The edited line isn't a valid entry.
+    Invalid(ParseError),
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EditError::NoSuchLine(line) => write!(formatter, "there is no line {}", line),
+            EditError::NoSuchMarker(ref marker) => write!(formatter, "there is no comment ‘{}’", marker),
+            EditError::NotAnEntry(line) => write!(formatter, "line {} is not an entry", line),
+            EditError::NotADisabledEntry(line) => write!(formatter, "line {} is not a commented out entry", line),
+            EditError::NotASchedule(ref schedule) => write!(formatter, "‘{}’ is not a schedule", schedule),
+            EditError::LineBreak => write!(formatter, "entries can't span lines"),
+            EditError::Invalid(ref error) => error.fmt(formatter),
+        }
+    }
+}
+
+impl Error for EditError {}
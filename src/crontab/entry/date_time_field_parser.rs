@@ -1,72 +1,303 @@
+use crontab::entry::day_rule::DayRule;
+use crontab::entry::expression;
 use crontab::entry::stepped_range::SteppedRange;
+use crontab::{Dialect, ParseError, Position};
+use hash::fnv1a;
 use std::ops::Range;
 
+/// Quartz years, which is as far as Quartz goes.
+const FIRST_YEAR: u16 = 1970;
+const LAST_YEAR: u16 = 2099;
+/// Days of the month `H` picks from, which exist in every month.
+const HASHED_DAYS_OF_MONTH: Range<u16> = 1..29;
+/// The most times a day of the week can occur in a month.
+const MAX_NTH: u32 = 5;
+
+/// Which days depending on the month a field accepts.
+#[derive(Clone, Copy, PartialEq)]
+enum Rules {
+    None,
+    /// `L`, `LW` and `15W`.
+    DayOfMonth,
+    /// `5L` and `5#3`.
+    DayOfWeek,
+}
+
 pub struct DateTimeFieldParser {
-    range: Range<u8>,
+    range: Range<u16>,
     wrap_around_at_end: bool,
+    /// Whether `?`, meaning no specific value, is accepted like `*`.
+    no_specific_value: bool,
+    /// Subtracted from numbers, for fields which count from one more than the values they stand for.
+    number_offset: u16,
+    rules: Rules,
+    /// The values `H` picks from without a range.
+    hashed_range: Range<u16>,
+    /// What `H` picks a value by.
+    hash: u64,
 }
 
 impl DateTimeFieldParser {
-    pub fn new(min: u8, max: u8) -> DateTimeFieldParser {
+    pub fn new(min: u16, max: u16) -> DateTimeFieldParser {
         DateTimeFieldParser {
             range: min..max + 1,
             wrap_around_at_end: false,
+            no_specific_value: false,
+            number_offset: 0,
+            rules: Rules::None,
+            hashed_range: min..max + 1,
+            hash: 0,
         }
     }
 
-    pub fn new_with_wrap_around(min: u8, max: u8) -> DateTimeFieldParser {
+    pub fn new_with_wrap_around(min: u16, max: u16) -> DateTimeFieldParser {
         DateTimeFieldParser {
-            range: min..max + 1,
             wrap_around_at_end: true,
+            ..DateTimeFieldParser::new(min, max)
         }
     }
 
-    pub fn parse_field(&self, string_value: &str) -> Vec<u8> {
-        let mut values = Vec::with_capacity((self.range.end - self.range.start) as usize);
+    /// Also accepts `?` for any value, as in the day fields of Quartz.
+    pub fn with_no_specific_value(self) -> DateTimeFieldParser {
+        DateTimeFieldParser {
+            no_specific_value: true,
+            ..self
+        }
+    }
 
-        string_value
-            .split(',')
-            .for_each(|part| values.append(&mut self.parse_list_entry(part)));
+    /// Numbers the values from one, as Quartz does with days of the week starting from Sunday.
+    pub fn numbered_from_one(self) -> DateTimeFieldParser {
+        DateTimeFieldParser {
+            number_offset: 1,
+            ..self
+        }
+    }
+
+    /// Also accepts `L` for the last day of the month, `LW` for its last weekday and `15W` for the weekday nearest
+    /// the 15th.
+    pub fn with_day_of_month_rules(self) -> DateTimeFieldParser {
+        DateTimeFieldParser {
+            rules: Rules::DayOfMonth,
+            ..self
+        }
+    }
+
+    /// Also accepts `5L` for the last Friday of the month and `5#3` for its third Friday.
+    pub fn with_day_of_week_rules(self) -> DateTimeFieldParser {
+        DateTimeFieldParser {
+            rules: Rules::DayOfWeek,
+            ..self
+        }
+    }
+
+    /// Picks the values of `H` by `hash`.
+    pub fn with_hash(self, hash: u64) -> DateTimeFieldParser {
+        DateTimeFieldParser { hash, ..self }
+    }
+
+    /// Parsers for the time fields of `dialect`, with `H` in each field picking a value by a hash of `seed` and the
+    /// field. The same seed always gives the same values, and different seeds usually spread out.
+    pub fn hashed_time_fields(dialect: Dialect, seed: &str) -> Vec<DateTimeFieldParser> {
+        DateTimeFieldParser::time_fields(dialect)
+            .into_iter()
+            .enumerate()
+            .map(|(index, parser)| {
+                let hash = fnv1a(seed.bytes().chain(Some(index as u8)));
+                parser.with_hash(mix(hash))
+            })
+            .collect()
+    }
+
+    /// Parsers for the time fields of `dialect`: seconds if it has them, minute, hour, day of month, month, day of
+    /// week, and year if it has them.
+    pub fn time_fields(dialect: Dialect) -> Vec<DateTimeFieldParser> {
+        // Only the dialects with seconds write an unrestricted day field as `?`
+        let day_field = |parser: DateTimeFieldParser| {
+            if dialect.has_seconds() {
+                parser.with_no_specific_value()
+            } else {
+                parser
+            }
+        };
+        let mut parsers = vec![];
+        if dialect.has_seconds() {
+            parsers.push(DateTimeFieldParser::new(0, 59));
+        }
+        parsers.push(DateTimeFieldParser::new(0, 59));
+        parsers.push(DateTimeFieldParser::new(0, 23));
+        parsers.push(day_field(DateTimeFieldParser {
+            hashed_range: HASHED_DAYS_OF_MONTH,
+            ..DateTimeFieldParser::new(1, 31).with_day_of_month_rules()
+        }));
+        parsers.push(DateTimeFieldParser::new(1, 12));
+        parsers.push(day_field(
+            if dialect == Dialect::Quartz {
+                DateTimeFieldParser::new(0, 6).numbered_from_one()
+            } else {
+                DateTimeFieldParser::new_with_wrap_around(0, 6)
+            }
+            .with_day_of_week_rules(),
+        ));
+        if dialect.has_year() {
+            parsers.push(DateTimeFieldParser::new(FIRST_YEAR, LAST_YEAR));
+        }
+        parsers
+    }
+
+    /// The values `*` stands for.
+    pub fn range(&self) -> Range<u16> {
+        self.range.clone()
+    }
+
+    /// The shortest way to write `values` and `rules`, with numbers rather than names. With `wildcard` it starts with
+    /// `*` exactly when that's `Some(true)`, for the fields where `*` means unrestricted rather than every value.
+    pub fn expression(&self, values: &[u16], rules: &[DayRule], wildcard: Option<bool>) -> String {
+        let mut items = vec![];
+        if !values.is_empty() {
+            items.push(self.values_expression(values, wildcard));
+        }
+        items.extend(rules.iter().map(|rule| rule.expression(self.number_offset)));
+        items.join(",")
+    }
+
+    fn values_expression(&self, values: &[u16], wildcard: Option<bool>) -> String {
+        let min = self.range.start + self.number_offset;
+        let max = self.range.end - 1 + self.number_offset;
+        let written: Vec<u16> = values.iter().map(|value| value + self.number_offset).collect();
+        if wildcard == Some(true) {
+            return expression::shortest_starred(&written, min, max);
+        }
+
+        let star = wildcard.is_none();
+        let shortest = expression::shortest(&written, min, max, star);
+        // Sunday can also be written as 7, which may join it to Saturday
+        if self.wrap_around_at_end && written[0] == min && written.len() > 1 {
+            let mut wrapped = written[1..].to_vec();
+            wrapped.push(max + 1);
+            let wrapped = expression::shortest(&wrapped, min, max, star);
+            if wrapped.len() < shortest.len() {
+                return wrapped;
+            }
+        }
+        shortest
+    }
+
+    /// The values in a field, leaving out any days which depend on the month.
+    pub fn parse_field(&self, string_value: &str) -> Result<Vec<u16>, ParseError> {
+        self.parse_day_field(string_value).map(|(values, _)| values)
+    }
+
+    /// The values in a field and the days in it which depend on the month, for the day fields.
+    pub fn parse_day_field(&self, string_value: &str) -> Result<(Vec<u16>, Vec<DayRule>), ParseError> {
+        let mut values = Vec::with_capacity((self.range.end - self.range.start) as usize);
+        let mut rules = vec![];
+
+        let mut offset = 0;
+        for part in string_value.split(',') {
+            match self.parse_rule(part) {
+                Some(rule) => rules.push(rule.map_err(|error| error.shifted(offset))?),
+                None => values.append(&mut self.parse_list_entry(part).map_err(|error| error.shifted(offset))?),
+            }
+            offset += part.len() + 1;
+        }
 
         values.sort_unstable();
         values.dedup();
+        rules.dedup();
+
+        Ok((values, rules))
+    }
+
+    /// The day `string_value` stands for if it's written with `L`, `W` or `#` in a field which accepts them.
+    fn parse_rule(&self, string_value: &str) -> Option<Result<DayRule, ParseError>> {
+        let upper = string_value.to_uppercase();
+        match self.rules {
+            Rules::None => None,
+            Rules::DayOfMonth if upper == "L" => Some(Ok(DayRule::LastDay)),
+            Rules::DayOfMonth if upper == "LW" => Some(Ok(DayRule::LastWeekday)),
+            Rules::DayOfMonth if upper.len() > 1 && upper.ends_with('W') => {
+                let day = &string_value[..string_value.len() - 1];
+                Some(self.parse_value(day).map(|day| DayRule::NearestWeekday(day as u8)))
+            }
+            Rules::DayOfMonth => None,
+            Rules::DayOfWeek => {
+                if let Some(separator) = string_value.find('#') {
+                    return Some(self.parse_nth_day_of_week(string_value, separator));
+                }
+                if upper.len() > 1 && upper.ends_with('L') {
+                    let day_of_week = &string_value[..string_value.len() - 1];
+                    return Some(self.parse_day_of_week(day_of_week).map(DayRule::LastDayOfWeek));
+                }
+                None
+            }
+        }
+    }
 
-        values
+    /// `5#3`, with the `#` at byte `separator`.
+    fn parse_nth_day_of_week(&self, string_value: &str, separator: usize) -> Result<DayRule, ParseError> {
+        let day_of_week = self.parse_day_of_week(&string_value[..separator])?;
+        let span = separator + 1..string_value.len();
+        let nth = DateTimeFieldParser::parse_number(&string_value[span.clone()])
+            .map_err(|error| error.shifted(span.start))?;
+        if !(1..=MAX_NTH).contains(&nth) {
+            return Err(ParseError::OutOfRange {
+                value: nth,
+                min: 1,
+                max: MAX_NTH as u16,
+                position: Position::new(span),
+            });
+        }
+        Ok(DayRule::NthDayOfWeek {
+            day_of_week,
+            nth: nth as u8,
+        })
     }
 
-    fn parse_list_entry(&self, string_value: &str) -> Vec<u8> {
-        let mut parts = string_value.splitn(2, '/');
-        let values = parts
-            .next()
-            .unwrap()
-            .replace("*", &format!("{}-{}", self.range.start, self.range.end - 1));
-
-        let values = match values.to_lowercase().as_ref() {
-            "sun" => "0",
-            "jan" | "mon" => "1",
-            "feb" | "tue" => "2",
-            "mar" | "wed" => "3",
-            "apr" | "thu" => "4",
-            "may" | "fri" => "5",
-            "jun" | "sat" => "6",
-            "jul" => "7",
-            "aug" => "8",
-            "sep" => "9",
-            "oct" => "10",
-            "nov" => "11",
-            "dec" => "12",
-            _ => &values,
-        };
+    /// A day of the week counted from Sunday as 0, even where 7 is allowed for Sunday too.
+    fn parse_day_of_week(&self, string_value: &str) -> Result<u8, ParseError> {
+        self.parse_value(string_value).map(|value| (value % 7) as u8)
+    }
 
-        let values: Range<u8> = self.parse_range(values);
+    /// Whether any item of the list `string_value` is written with `H`, rather than merely containing one like `THU`.
+    pub fn is_hashed(string_value: &str) -> bool {
+        string_value.split(',').any(DateTimeFieldParser::is_hashed_item)
+    }
 
-        let step = match parts.next() {
-            Some(string_value) => string_value.parse::<u8>().unwrap(),
-            None => 1,
-        };
+    /// Whether the range of the list item `string_value`, before any step, is `H` or `H(…)`.
+    fn is_hashed_item(string_value: &str) -> bool {
+        let range_value = string_value.split('/').next().unwrap();
+        range_value == "H" || range_value.starts_with("H(")
+    }
+
+    /// `string_value` with each `H` replaced by the values it stands for, such as `7-59/15` for `H/15`. Anything which
+    /// doesn't parse is left as it is.
+    pub fn resolve_hashes(&self, string_value: &str) -> String {
+        let parts: Vec<String> = string_value
+            .split(',')
+            .map(|part| match self.parse_stepped_range(part) {
+                Ok((values, _)) if DateTimeFieldParser::is_hashed_item(part) && values.len() == 1 => {
+                    (values.start + self.number_offset).to_string()
+                }
+                Ok((values, step)) if DateTimeFieldParser::is_hashed_item(part) => format!(
+                    "{}-{}/{}",
+                    values.start + self.number_offset,
+                    values.end - 1 + self.number_offset,
+                    step
+                ),
+                _ => part.to_string(),
+            })
+            .collect();
+        parts.join(",")
+    }
+
+    fn parse_list_entry(&self, string_value: &str) -> Result<Vec<u16>, ParseError> {
+        let (values, step) = self.parse_stepped_range(string_value)?;
 
         // TODO: Use step_by when stable
-        let mut values: Vec<u8> = SteppedRange::new(values.start, values.end, step).collect();
+        let mut values: Vec<u16> = SteppedRange::new(u32::from(values.start), u32::from(values.end), step)
+            .expect("steps are checked when parsed")
+            .map(|value| value as u16)
+            .collect();
 
         let last_value = values.pop().unwrap();
         if last_value == self.range.end && self.wrap_around_at_end {
@@ -75,255 +306,565 @@ impl DateTimeFieldParser {
             values.push(last_value);
         }
 
-        self.verify_range(values[0]);
-        self.verify_range(values[values.len() - 1]);
+        Ok(values)
+    }
+
+    /// The values of a list entry before stepping, and the step.
+    pub fn parse_stepped_range(&self, string_value: &str) -> Result<(Range<u16>, u32), ParseError> {
+        let mut parts = string_value.splitn(2, '/');
+        let range_value = parts.next().unwrap();
 
-        values
+        let step = match parts.next() {
+            Some(step_value) => {
+                let offset = range_value.len() + 1;
+                let step = DateTimeFieldParser::parse_number(step_value).map_err(|error| error.shifted(offset))?;
+                if step == 0 {
+                    return Err(ParseError::ZeroStep {
+                        position: Position::new(offset..string_value.len()),
+                    });
+                }
+                // Any step past the end of the range yields only the first value, and can't overflow
+                Some(step.min(u32::from(self.range.end)))
+            }
+            None => None,
+        };
+
+        if DateTimeFieldParser::is_hashed_item(range_value) {
+            return self.parse_hashed(range_value, step);
+        }
+        Ok((self.parse_range(range_value)?, step.unwrap_or(1)))
+    }
+
+    /// `H` or `H(0-29)`, as in Jenkins: a value picked by the hash instead of written, or with a step, the first value
+    /// of the steps.
+    fn parse_hashed(&self, range_value: &str, step: Option<u32>) -> Result<(Range<u16>, u32), ParseError> {
+        let range = if range_value == "H" {
+            self.hashed_range.clone()
+        } else if range_value.ends_with(')') {
+            let inner = &range_value[2..range_value.len() - 1];
+            self.parse_range(inner).map_err(|error| error.shifted(2))?
+        } else {
+            return Err(ParseError::BadNumber {
+                value: range_value.to_string(),
+                position: Position::new(0..range_value.len()),
+            });
+        };
+
+        let size = u64::from(range.end - range.start);
+        match step {
+            Some(step) => {
+                let first = range.start + (self.hash % size.min(u64::from(step))) as u16;
+                Ok((first..range.end, step))
+            }
+            None => {
+                let value = range.start + (self.hash % size) as u16;
+                Ok((value..value + 1, 1))
+            }
+        }
     }
 
-    fn parse_range(&self, values: &str) -> Range<u8> {
-        let mut range_or_value = values.splitn(2, '-').map(|part| part.parse().unwrap());
-        let first = range_or_value.next().expect("Empty range");
+    fn parse_range(&self, values: &str) -> Result<Range<u16>, ParseError> {
+        if values == "*" || (values == "?" && self.no_specific_value) {
+            return Ok(self.range.clone());
+        }
+
+        let mut range_or_value = values.splitn(2, '-');
+        let first_value = range_or_value.next().unwrap();
+        let first = self.parse_value(first_value)?;
         let last = match range_or_value.next() {
-            Some(value) => value,
+            Some(last_value) => {
+                let offset = first_value.len() + 1;
+                self.parse_value(last_value).map_err(|error| error.shifted(offset))?
+            }
             None => first,
         };
 
+        if last < first {
+            return Err(ParseError::ReversedRange {
+                first: first + self.number_offset,
+                last: last + self.number_offset,
+                position: Position::new(0..values.len()),
+            });
+        }
+
         // TODO: Use inclusive range when stable
-        first..last + 1
+        Ok(first..last + 1)
+    }
+
+    fn parse_value(&self, string_value: &str) -> Result<u16, ParseError> {
+        let name = match string_value.to_lowercase().as_ref() {
+            "sun" => Some(0),
+            "jan" | "mon" => Some(1),
+            "feb" | "tue" => Some(2),
+            "mar" | "wed" => Some(3),
+            "apr" | "thu" => Some(4),
+            "may" | "fri" => Some(5),
+            "jun" | "sat" => Some(6),
+            "jul" => Some(7),
+            "aug" => Some(8),
+            "sep" => Some(9),
+            "oct" => Some(10),
+            "nov" => Some(11),
+            "dec" => Some(12),
+            _ => None,
+        };
+        let value = match name {
+            Some(value) => value + u32::from(self.number_offset),
+            None => DateTimeFieldParser::parse_number(string_value)?,
+        };
+        self.verify_range(value, 0..string_value.len())
     }
 
-    fn verify_range(&self, value: u8) {
-        assert!(self.range.start <= value);
-        assert!(self.range.end > value);
+    fn parse_number(string_value: &str) -> Result<u32, ParseError> {
+        string_value.parse().map_err(|_| ParseError::BadNumber {
+            value: string_value.to_string(),
+            position: Position::new(0..string_value.len()),
+        })
+    }
+
+    /// Checks `value` as written, and returns the value it stands for.
+    fn verify_range(&self, value: u32, span: Range<usize>) -> Result<u16, ParseError> {
+        let min = self.range.start + self.number_offset;
+        let max = if self.wrap_around_at_end {
+            self.range.end
+        } else {
+            self.range.end - 1
+        } + self.number_offset;
+        if value < u32::from(min) || value > u32::from(max) {
+            return Err(ParseError::OutOfRange {
+                value,
+                min,
+                max,
+                position: Position::new(span),
+            });
+        }
+        Ok(value as u16 - self.number_offset)
     }
 }
 
+/// The MurmurHash3 finalizer, so that every bit of `hash` affects the low bits `H` picks values by.
+fn mix(hash: u64) -> u64 {
+    let hash = (hash ^ (hash >> 33)).wrapping_mul(0xff51_afd7_ed55_8ccd);
+    let hash = (hash ^ (hash >> 33)).wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
+
 #[cfg(test)]
 mod tests {
     use super::DateTimeFieldParser;
+    use crontab::entry::day_rule::DayRule;
+    use crontab::{Dialect, ParseError, Position};
 
     #[test]
     fn should_parse_complex_pattern() {
         let parser = DateTimeFieldParser::new(1, 12);
-        assert_eq!(parser.parse_field("5-9/2,1,*/5"), vec![1, 5, 6, 7, 9, 11]);
+        assert_eq!(parser.parse_field("5-9/2,1,*/5"), Ok(vec![1, 5, 6, 7, 9, 11]));
     }
 
     #[test]
     fn should_parse_comma_separated_numbers() {
         let parser = DateTimeFieldParser::new(0, 23);
-        assert_eq!(parser.parse_field("0,23"), vec![0, 23]);
+        assert_eq!(parser.parse_field("0,23"), Ok(vec![0, 23]));
     }
 
     #[test]
     fn should_parse_range_with_step() {
         let parser = DateTimeFieldParser::new(0, 23);
-        assert_eq!(parser.parse_list_entry("1-7/2"), vec![1, 3, 5, 7]);
+        assert_eq!(parser.parse_list_entry("1-7/2"), Ok(vec![1, 3, 5, 7]));
     }
 
     #[test]
     fn should_parse_asterisk() {
         let parser = DateTimeFieldParser::new(1, 12);
-        assert_eq!(parser.parse_list_entry("*/4"), vec![1, 5, 9]);
+        assert_eq!(parser.parse_list_entry("*/4"), Ok(vec![1, 5, 9]));
     }
 
     #[test]
     fn should_parse_january_name() {
         let parser = DateTimeFieldParser::new(1, 12);
-        assert_eq!(parser.parse_list_entry("Jan"), vec![1]);
+        assert_eq!(parser.parse_list_entry("Jan"), Ok(vec![1]));
     }
 
     #[test]
     fn should_parse_february_name() {
         let parser = DateTimeFieldParser::new(1, 12);
-        assert_eq!(parser.parse_list_entry("Feb"), vec![2]);
+        assert_eq!(parser.parse_list_entry("Feb"), Ok(vec![2]));
     }
 
     #[test]
     fn should_parse_march_name() {
         let parser = DateTimeFieldParser::new(1, 12);
-        assert_eq!(parser.parse_list_entry("Mar"), vec![3]);
+        assert_eq!(parser.parse_list_entry("Mar"), Ok(vec![3]));
     }
 
     #[test]
     fn should_parse_april_name() {
         let parser = DateTimeFieldParser::new(1, 12);
-        assert_eq!(parser.parse_list_entry("Apr"), vec![4]);
+        assert_eq!(parser.parse_list_entry("Apr"), Ok(vec![4]));
     }
 
     #[test]
     fn should_parse_may_name() {
         let parser = DateTimeFieldParser::new(1, 12);
-        assert_eq!(parser.parse_list_entry("May"), vec![5]);
+        assert_eq!(parser.parse_list_entry("May"), Ok(vec![5]));
     }
 
     #[test]
     fn should_parse_june_name() {
         let parser = DateTimeFieldParser::new(1, 12);
-        assert_eq!(parser.parse_list_entry("Jun"), vec![6]);
+        assert_eq!(parser.parse_list_entry("Jun"), Ok(vec![6]));
     }
 
     #[test]
     fn should_parse_july_name() {
         let parser = DateTimeFieldParser::new(1, 12);
-        assert_eq!(parser.parse_list_entry("Jul"), vec![7]);
+        assert_eq!(parser.parse_list_entry("Jul"), Ok(vec![7]));
     }
 
     #[test]
     fn should_parse_august_name() {
         let parser = DateTimeFieldParser::new(1, 12);
-        assert_eq!(parser.parse_list_entry("Aug"), vec![8]);
+        assert_eq!(parser.parse_list_entry("Aug"), Ok(vec![8]));
     }
 
     #[test]
     fn should_parse_september_name() {
         let parser = DateTimeFieldParser::new(1, 12);
-        assert_eq!(parser.parse_list_entry("Sep"), vec![9]);
+        assert_eq!(parser.parse_list_entry("Sep"), Ok(vec![9]));
     }
 
     #[test]
     fn should_parse_october_name() {
         let parser = DateTimeFieldParser::new(1, 12);
-        assert_eq!(parser.parse_list_entry("Oct"), vec![10]);
+        assert_eq!(parser.parse_list_entry("Oct"), Ok(vec![10]));
     }
 
     #[test]
     fn should_parse_november_name() {
         let parser = DateTimeFieldParser::new(1, 12);
-        assert_eq!(parser.parse_list_entry("Nov"), vec![11]);
+        assert_eq!(parser.parse_list_entry("Nov"), Ok(vec![11]));
     }
 
     #[test]
     fn should_parse_december_name() {
         let parser = DateTimeFieldParser::new(1, 12);
-        assert_eq!(parser.parse_list_entry("Dec"), vec![12]);
+        assert_eq!(parser.parse_list_entry("Dec"), Ok(vec![12]));
     }
 
     #[test]
     fn should_parse_month_name_case_insensitively() {
         let parser = DateTimeFieldParser::new(1, 12);
-        assert_eq!(parser.parse_list_entry("dEC"), vec![12]);
+        assert_eq!(parser.parse_list_entry("dEC"), Ok(vec![12]));
     }
 
     #[test]
     fn should_parse_sunday_name() {
         let parser = DateTimeFieldParser::new_with_wrap_around(0, 6);
-        assert_eq!(parser.parse_list_entry("Sun"), vec![0]);
+        assert_eq!(parser.parse_list_entry("Sun"), Ok(vec![0]));
     }
 
     #[test]
     fn should_parse_monday_name() {
         let parser = DateTimeFieldParser::new_with_wrap_around(0, 6);
-        assert_eq!(parser.parse_list_entry("Mon"), vec![1]);
+        assert_eq!(parser.parse_list_entry("Mon"), Ok(vec![1]));
     }
 
     #[test]
     fn should_parse_tuesday_name() {
         let parser = DateTimeFieldParser::new_with_wrap_around(0, 6);
-        assert_eq!(parser.parse_list_entry("Tue"), vec![2]);
+        assert_eq!(parser.parse_list_entry("Tue"), Ok(vec![2]));
     }
 
     #[test]
     fn should_parse_wednesday_name() {
         let parser = DateTimeFieldParser::new_with_wrap_around(0, 6);
-        assert_eq!(parser.parse_list_entry("Wed"), vec![3]);
+        assert_eq!(parser.parse_list_entry("Wed"), Ok(vec![3]));
     }
 
     #[test]
     fn should_parse_thursday_name() {
         let parser = DateTimeFieldParser::new_with_wrap_around(0, 6);
-        assert_eq!(parser.parse_list_entry("Thu"), vec![4]);
+        assert_eq!(parser.parse_list_entry("Thu"), Ok(vec![4]));
     }
 
     #[test]
     fn should_parse_friday_name() {
         let parser = DateTimeFieldParser::new_with_wrap_around(0, 6);
-        assert_eq!(parser.parse_list_entry("Fri"), vec![5]);
+        assert_eq!(parser.parse_list_entry("Fri"), Ok(vec![5]));
     }
 
     #[test]
     fn should_parse_saturday_name() {
         let parser = DateTimeFieldParser::new_with_wrap_around(0, 6);
-        assert_eq!(parser.parse_list_entry("Sat"), vec![6]);
+        assert_eq!(parser.parse_list_entry("Sat"), Ok(vec![6]));
     }
 
     #[test]
     fn should_parse_week_day_name_case_insensitively() {
         let parser = DateTimeFieldParser::new_with_wrap_around(0, 6);
-        assert_eq!(parser.parse_list_entry("sAT"), vec![6]);
+        assert_eq!(parser.parse_list_entry("sAT"), Ok(vec![6]));
     }
 
     #[test]
     fn should_remove_duplicates() {
         let parser = DateTimeFieldParser::new(1, 2);
-        assert_eq!(parser.parse_field("1,1,2,2,2"), vec![1, 2]);
+        assert_eq!(parser.parse_field("1,1,2,2,2"), Ok(vec![1, 2]));
     }
 
     #[test]
     fn should_sort_values() {
         let parser = DateTimeFieldParser::new(1, 2);
-        assert_eq!(parser.parse_field("2,1"), vec![1, 2]);
+        assert_eq!(parser.parse_field("2,1"), Ok(vec![1, 2]));
     }
 
     #[test]
     fn should_parse_wraparound_sunday() {
         let parser = DateTimeFieldParser::new_with_wrap_around(0, 6);
-        assert_eq!(parser.parse_field("7"), vec![0]);
+        assert_eq!(parser.parse_field("7"), Ok(vec![0]));
     }
 
     #[test]
     fn should_parse_week_range_with_sunday_at_end() {
         let parser = DateTimeFieldParser::new_with_wrap_around(0, 6);
-        assert_eq!(parser.parse_field("4-7"), vec![0, 4, 5, 6]);
+        assert_eq!(parser.parse_field("4-7"), Ok(vec![0, 4, 5, 6]));
     }
 
     #[test]
     fn should_parse_week_range_with_sunday_at_both_sides() {
         let parser = DateTimeFieldParser::new_with_wrap_around(0, 6);
-        assert_eq!(parser.parse_field("0-7"), vec![0, 1, 2, 3, 4, 5, 6]);
+        assert_eq!(parser.parse_field("0-7"), Ok(vec![0, 1, 2, 3, 4, 5, 6]));
     }
 
     #[test]
     fn should_parse_range() {
         let parser = DateTimeFieldParser::new(0, 23);
-        assert_eq!(parser.parse_range("1-3"), { 1..4 });
+        assert_eq!(parser.parse_range("1-3"), Ok(1..4));
     }
 
     #[test]
-    #[should_panic]
     fn should_fail_verification_below_min() {
         let parser = DateTimeFieldParser::new(1, 12);
-        parser.verify_range(0)
+        assert_eq!(
+            parser.verify_range(0, 0..1),
+            Err(ParseError::OutOfRange {
+                value: 0,
+                min: 1,
+                max: 12,
+                position: Position::new(0..1),
+            })
+        );
     }
 
     #[test]
     fn should_verify_at_min() {
         let parser = DateTimeFieldParser::new(0, 23);
-        parser.verify_range(0);
+        assert_eq!(parser.verify_range(0, 0..1), Ok(0));
     }
 
     #[test]
     fn should_verify_between_min_and_max() {
         let parser = DateTimeFieldParser::new(0, 23);
-        parser.verify_range(12);
+        assert_eq!(parser.verify_range(12, 0..2), Ok(12));
     }
 
     #[test]
     fn should_verify_at_both_min_and_max() {
         let parser = DateTimeFieldParser::new(1, 1);
-        parser.verify_range(1);
+        assert_eq!(parser.verify_range(1, 0..1), Ok(1));
     }
 
     #[test]
     fn should_verify_at_max() {
         let parser = DateTimeFieldParser::new(0, 23);
-        parser.verify_range(23);
+        assert_eq!(parser.verify_range(23, 0..2), Ok(23));
     }
 
     #[test]
-    #[should_panic]
     fn should_fail_verification_above_max() {
         let parser = DateTimeFieldParser::new(0, 23);
-        parser.verify_range(24);
+        assert!(parser.verify_range(24, 0..2).is_err());
+    }
+
+    #[test]
+    fn should_accept_question_mark_only_where_allowed() {
+        let parser = DateTimeFieldParser::new(1, 31).with_no_specific_value();
+        assert_eq!(parser.parse_field("?").unwrap().len(), 31);
+        assert!(DateTimeFieldParser::new(0, 59).parse_field("?").is_err());
+    }
+
+    #[test]
+    fn should_count_quartz_days_of_week_from_sunday_as_one() {
+        let parser = DateTimeFieldParser::new(0, 6).numbered_from_one();
+        assert_eq!(parser.parse_field("1,7"), Ok(vec![0, 6]));
+        assert_eq!(parser.parse_field("MON-FRI"), Ok(vec![1, 2, 3, 4, 5]));
+        assert_eq!(
+            parser.parse_field("0"),
+            Err(ParseError::OutOfRange {
+                value: 0,
+                min: 1,
+                max: 7,
+                position: Position::new(0..1),
+            })
+        );
+    }
+
+    #[test]
+    fn should_parse_day_of_month_rules() {
+        let parser = DateTimeFieldParser::new(1, 31).with_day_of_month_rules();
+        assert_eq!(
+            parser.parse_day_field("1,L,LW,15W"),
+            Ok((
+                vec![1],
+                vec![DayRule::LastDay, DayRule::LastWeekday, DayRule::NearestWeekday(15)]
+            ))
+        );
+        assert_eq!(
+            parser.parse_field("1,32W"),
+            Err(ParseError::OutOfRange {
+                value: 32,
+                min: 1,
+                max: 31,
+                position: Position::new(2..4),
+            })
+        );
+        assert!(DateTimeFieldParser::new(1, 31).parse_field("L").is_err());
+    }
+
+    #[test]
+    fn should_parse_day_of_week_rules() {
+        let parser = DateTimeFieldParser::new_with_wrap_around(0, 6).with_day_of_week_rules();
+        assert_eq!(
+            parser.parse_day_field("5#3,7L,FRIL"),
+            Ok((
+                vec![],
+                vec![
+                    DayRule::NthDayOfWeek { day_of_week: 5, nth: 3 },
+                    DayRule::LastDayOfWeek(0),
+                    DayRule::LastDayOfWeek(5)
+                ]
+            ))
+        );
+        assert_eq!(
+            parser.parse_field("1-5,5#6"),
+            Err(ParseError::OutOfRange {
+                value: 6,
+                min: 1,
+                max: 5,
+                position: Position::new(6..7),
+            })
+        );
+    }
+
+    #[test]
+    fn should_pick_hashed_value_within_range() {
+        let parser = DateTimeFieldParser::new(0, 59).with_hash(1234);
+        assert_eq!(parser.parse_field("H"), Ok(vec![34]));
+        assert_eq!(parser.parse_field("H(0-29)"), Ok(vec![4]));
+        assert_eq!(parser.parse_field("H/15"), Ok(vec![4, 19, 34, 49]));
+        assert_eq!(parser.parse_field("H(30-59)/10"), Ok(vec![34, 44, 54]));
+        assert_eq!(
+            parser.parse_field("H(30-60)"),
+            Err(ParseError::OutOfRange {
+                value: 60,
+                min: 0,
+                max: 59,
+                position: Position::new(5..7),
+            })
+        );
+    }
+
+    #[test]
+    fn should_spread_hashed_values_by_seed() {
+        let values = |seed: &str| {
+            DateTimeFieldParser::hashed_time_fields(Dialect::Vixie, seed)
+                .iter()
+                .map(|parser| parser.parse_field("H").unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values("backup"), values("backup"));
+        assert_ne!(values("backup"), values("web-1 backup"));
+        for seed in &["a", "b", "c", "d"] {
+            assert!(values(seed)[2][0] <= 28);
+        }
+    }
+
+    #[test]
+    fn should_resolve_hashes_in_field() {
+        let parser = DateTimeFieldParser::new(0, 59).with_hash(1234);
+        assert_eq!(parser.resolve_hashes("H/15,5-7,H"), "4-59/15,5-7,34");
+    }
+
+    #[test]
+    fn should_parse_years() {
+        let parser = DateTimeFieldParser::new(1970, 2099);
+        assert_eq!(parser.parse_field("2026-2030/2"), Ok(vec![2026, 2028, 2030]));
+    }
+
+    #[test]
+    fn should_parse_named_range() {
+        let parser = DateTimeFieldParser::new_with_wrap_around(0, 6);
+        assert_eq!(parser.parse_field("Mon-Fri"), Ok(vec![1, 2, 3, 4, 5]));
+    }
+
+    #[test]
+    fn should_report_bad_number_with_span() {
+        let parser = DateTimeFieldParser::new(0, 59);
+        assert_eq!(
+            parser.parse_field("1,x5"),
+            Err(ParseError::BadNumber {
+                value: "x5".to_string(),
+                position: Position::new(2..4),
+            })
+        );
+    }
+
+    #[test]
+    fn should_report_out_of_range_end_with_span() {
+        let parser = DateTimeFieldParser::new(0, 23);
+        assert_eq!(
+            parser.parse_field("0,22-24"),
+            Err(ParseError::OutOfRange {
+                value: 24,
+                min: 0,
+                max: 23,
+                position: Position::new(5..7),
+            })
+        );
+    }
+
+    #[test]
+    fn should_report_zero_step_with_span() {
+        let parser = DateTimeFieldParser::new(0, 59);
+        assert_eq!(
+            parser.parse_field("*/0"),
+            Err(ParseError::ZeroStep {
+                position: Position::new(2..3),
+            })
+        );
+    }
+
+    #[test]
+    fn should_report_reversed_range() {
+        let parser = DateTimeFieldParser::new(0, 59);
+        assert_eq!(
+            parser.parse_field("30-10"),
+            Err(ParseError::ReversedRange {
+                first: 30,
+                last: 10,
+                position: Position::new(0..5),
+            })
+        );
+    }
+
+    #[test]
+    fn should_report_empty_list_entry() {
+        let parser = DateTimeFieldParser::new(0, 59);
+        assert!(parser.parse_field("1,,2").is_err());
+    }
+
+    #[test]
+    fn should_handle_huge_step() {
+        let parser = DateTimeFieldParser::new(0, 59);
+        assert_eq!(parser.parse_field("5/4294967295"), Ok(vec![5]));
     }
 }
@@ -0,0 +1,147 @@
+use super::description::{ordinal, DAYS_OF_MONTH, DAYS_OF_WEEK};
+
+/// A day which depends on the month it's in, written with `L`, `W` or `#` in a day field.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DayRule {
+    /// `L` in the day-of-month field: the last day of the month.
+    LastDay,
+    /// `LW`: the last Monday through Friday of the month.
+    LastWeekday,
+    /// `15W`: the Monday through Friday nearest to a day of the month, without leaving the month. Nothing in months
+    /// which don't have the day.
+    NearestWeekday(u8),
+    /// `5#3`: the third Friday of the month, if there is one. Days of the week count from Sunday as 0.
+    NthDayOfWeek { day_of_week: u8, nth: u8 },
+    /// `5L`: the last Friday of the month.
+    LastDayOfWeek(u8),
+}
+
+impl DayRule {
+    /// Whether this is written in the day-of-week field, and so counts as a day of the week when matching.
+    pub fn is_day_of_week(self) -> bool {
+        match self {
+            DayRule::NthDayOfWeek { .. } | DayRule::LastDayOfWeek(_) => true,
+            DayRule::LastDay | DayRule::LastWeekday | DayRule::NearestWeekday(_) => false,
+        }
+    }
+
+    /// The day of a month with `length` days whose first day is `first_weekday` (counted from Sunday as 0).
+    pub fn day_in(self, first_weekday: u32, length: u32) -> Option<u32> {
+        let weekday = |day: u32| (first_weekday + day - 1) % 7;
+        match self {
+            DayRule::LastDay => Some(length),
+            DayRule::LastWeekday => match weekday(length) {
+                0 => Some(length - 2),
+                6 => Some(length - 1),
+                _ => Some(length),
+            },
+            DayRule::NearestWeekday(day) => {
+                let day = u32::from(day);
+                match weekday(day) {
+                    _ if day > length => None,
+                    // Saturday the 1st moves forward to Monday the 3rd, and Sunday the last back to Friday
+                    0 if day == length => Some(day - 2),
+                    0 => Some(day + 1),
+                    6 if day == 1 => Some(day + 2),
+                    6 => Some(day - 1),
+                    _ => Some(day),
+                }
+            }
+            DayRule::NthDayOfWeek { day_of_week, nth } => {
+                let first = 1 + (u32::from(day_of_week) + 7 - first_weekday) % 7;
+                Some(first + 7 * (u32::from(nth) - 1)).filter(|&day| day <= length)
+            }
+            DayRule::LastDayOfWeek(day_of_week) => Some(length - (weekday(length) + 7 - u32::from(day_of_week)) % 7),
+        }
+    }
+
+    /// How this is written, with days of the week numbered from `first_day_of_week` for Sunday.
+    pub fn expression(self, first_day_of_week: u16) -> String {
+        let day_of_week = |day_of_week: u8| u16::from(day_of_week) + first_day_of_week;
+        match self {
+            DayRule::LastDay => "L".to_string(),
+            DayRule::LastWeekday => "LW".to_string(),
+            DayRule::NearestWeekday(day) => format!("{}W", day),
+            DayRule::NthDayOfWeek { day_of_week: day, nth } => format!("{}#{}", day_of_week(day), nth),
+            DayRule::LastDayOfWeek(day) => format!("{}L", day_of_week(day)),
+        }
+    }
+
+    /// An English phrase, such as "the last day of the month" or "the 3rd Friday of the month".
+    pub fn describe(self) -> String {
+        match self {
+            DayRule::LastDay => "the last day of the month".to_string(),
+            DayRule::LastWeekday => "the last weekday of the month".to_string(),
+            DayRule::NearestWeekday(day) => {
+                format!("the weekday nearest {}", DAYS_OF_MONTH.describe(&[u16::from(day)]))
+            }
+            DayRule::NthDayOfWeek { day_of_week, nth } => format!(
+                "the {} {} of the month",
+                ordinal(u16::from(nth)),
+                DAYS_OF_WEEK.describe(&[u16::from(day_of_week)])
+            ),
+            DayRule::LastDayOfWeek(day_of_week) => format!(
+                "the last {} of the month",
+                DAYS_OF_WEEK.describe(&[u16::from(day_of_week)])
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DayRule;
+
+    // October 2026 starts on a Thursday and has 31 days, ending on a Saturday
+    const FIRST_WEEKDAY: u32 = 4;
+    const LENGTH: u32 = 31;
+
+    #[test]
+    fn should_find_last_day_and_last_weekday() {
+        assert_eq!(DayRule::LastDay.day_in(FIRST_WEEKDAY, LENGTH), Some(31));
+        assert_eq!(DayRule::LastWeekday.day_in(FIRST_WEEKDAY, LENGTH), Some(30));
+    }
+
+    #[test]
+    fn should_find_nearest_weekday_within_month() {
+        // The 3rd is a Saturday, the 4th a Sunday and the 31st a Saturday
+        assert_eq!(DayRule::NearestWeekday(3).day_in(FIRST_WEEKDAY, LENGTH), Some(2));
+        assert_eq!(DayRule::NearestWeekday(4).day_in(FIRST_WEEKDAY, LENGTH), Some(5));
+        assert_eq!(DayRule::NearestWeekday(15).day_in(FIRST_WEEKDAY, LENGTH), Some(15));
+        assert_eq!(DayRule::NearestWeekday(31).day_in(FIRST_WEEKDAY, LENGTH), Some(30));
+        assert_eq!(DayRule::NearestWeekday(31).day_in(FIRST_WEEKDAY, 30), None);
+        // A month starting on Saturday
+        assert_eq!(DayRule::NearestWeekday(1).day_in(6, 30), Some(3));
+    }
+
+    #[test]
+    fn should_find_nth_and_last_day_of_week() {
+        let third_friday = DayRule::NthDayOfWeek { day_of_week: 5, nth: 3 };
+        assert_eq!(third_friday.day_in(FIRST_WEEKDAY, LENGTH), Some(16));
+        let fifth_friday = DayRule::NthDayOfWeek { day_of_week: 5, nth: 5 };
+        assert_eq!(fifth_friday.day_in(FIRST_WEEKDAY, LENGTH), Some(30));
+        let fifth_monday = DayRule::NthDayOfWeek { day_of_week: 1, nth: 5 };
+        assert_eq!(fifth_monday.day_in(FIRST_WEEKDAY, LENGTH), None);
+        assert_eq!(DayRule::LastDayOfWeek(5).day_in(FIRST_WEEKDAY, LENGTH), Some(30));
+        assert_eq!(DayRule::LastDayOfWeek(6).day_in(FIRST_WEEKDAY, LENGTH), Some(31));
+    }
+
+    #[test]
+    fn should_write_rules_as_parsed() {
+        assert_eq!(DayRule::NearestWeekday(15).expression(0), "15W");
+        assert_eq!(DayRule::NthDayOfWeek { day_of_week: 5, nth: 3 }.expression(0), "5#3");
+        assert_eq!(DayRule::LastDayOfWeek(5).expression(1), "6L");
+    }
+
+    #[test]
+    fn should_describe_rules() {
+        assert_eq!(
+            DayRule::NearestWeekday(15).describe(),
+            "the weekday nearest day-of-month 15"
+        );
+        assert_eq!(
+            DayRule::NthDayOfWeek { day_of_week: 5, nth: 3 }.describe(),
+            "the 3rd Friday of the month"
+        );
+    }
+}
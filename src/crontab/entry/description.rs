@@ -0,0 +1,337 @@
+//! English phrases for the values of a time field.
+
+const DAY_NAMES: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+const MONTH_NAMES: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// How the values of one field are called.
+pub struct Unit {
+    singular: &'static str,
+    plural: &'static str,
+    min: u16,
+    max: u16,
+    /// Converts a value to its name, for fields which have them.
+    name: Option<fn(u16) -> &'static str>,
+    /// Whether steps read as lengths of time, as in "every 15 minutes", rather than as in "every 2nd hour".
+    counted: bool,
+}
+
+pub const SECONDS: Unit = Unit::numbered("second", "seconds", 0, 59).counted();
+pub const MINUTES: Unit = Unit::numbered("minute", "minutes", 0, 59).counted();
+pub const HOURS: Unit = Unit::numbered("hour", "hours", 0, 23);
+pub const DAYS_OF_MONTH: Unit = Unit::numbered("day-of-month", "days-of-month", 1, 31);
+pub const MONTHS: Unit = Unit::named("month", 1, 12, |month| MONTH_NAMES[usize::from(month) - 1]);
+pub const YEARS: Unit = Unit::numbered("year", "years", 1970, 2099);
+/// Days of the week counted from Sunday, as in crontabs.
+pub const DAYS_OF_WEEK: Unit = Unit::named("day-of-week", 0, 6, |day| DAY_NAMES[usize::from(day % 7)]);
+/// Days of the week counted from Monday, with Sunday as 7, so that weekends are consecutive.
+pub const DAYS_OF_WEEK_FROM_MONDAY: Unit = Unit::named("day-of-week", 1, 7, |day| DAY_NAMES[usize::from(day % 7)]);
+
+impl Unit {
+    const fn numbered(singular: &'static str, plural: &'static str, min: u16, max: u16) -> Unit {
+        Unit {
+            singular,
+            plural,
+            min,
+            max,
+            name: None,
+            counted: false,
+        }
+    }
+
+    const fn named(singular: &'static str, min: u16, max: u16, name: fn(u16) -> &'static str) -> Unit {
+        Unit {
+            singular,
+            plural: singular,
+            min,
+            max,
+            name: Some(name),
+            counted: false,
+        }
+    }
+
+    const fn counted(self) -> Unit {
+        Unit { counted: true, ..self }
+    }
+
+    /// Whether `values` contains every value of the field.
+    pub fn is_full(&self, values: &[u16]) -> bool {
+        (self.min..=self.max).all(|value| values.contains(&value))
+    }
+
+    /// Describes `values`, such as "minutes 1 and 7", "every 15 minutes" or "every 2nd hour from 9 through 17".
+    pub fn describe(&self, values: &[u16]) -> String {
+        let mut phrases = vec![];
+        let mut singles = vec![];
+        for piece in pieces(values) {
+            match piece {
+                Piece::Single(value) => singles.push(value),
+                Piece::Progression { first, last, step } => {
+                    if !singles.is_empty() {
+                        phrases.push(self.describe_singles(&singles));
+                        singles.clear();
+                    }
+                    phrases.push(self.describe_progression(first, last, step));
+                }
+            }
+        }
+        if !singles.is_empty() {
+            phrases.push(self.describe_singles(&singles));
+        }
+        list(&phrases)
+    }
+
+    /// The number of phrases `describe` joins.
+    pub fn complexity(&self, values: &[u16]) -> usize {
+        pieces(values).len()
+    }
+
+    fn value(&self, value: u16) -> String {
+        match self.name {
+            Some(name) => name(value).to_string(),
+            None => value.to_string(),
+        }
+    }
+
+    fn describe_singles(&self, values: &[u16]) -> String {
+        let names: Vec<String> = values.iter().map(|&value| self.value(value)).collect();
+        match (self.name, values.len()) {
+            (Some(_), _) => list(&names),
+            (None, 1) => format!("{} {}", self.singular, names[0]),
+            (None, _) => format!("{} {}", self.plural, list(&names)),
+        }
+    }
+
+    fn describe_progression(&self, first: u16, last: u16, step: u16) -> String {
+        let through = format!("{} through {}", self.value(first), self.value(last));
+        let counted = format!("every {} {}", step, self.plural);
+        let ordinal = format!("every {} {}", ordinal(step), self.singular);
+        let whole = first == self.min && u32::from(last) + u32::from(step) > u32::from(self.max);
+        match step {
+            1 if self.name.is_some() => through,
+            1 => format!("{} {}", self.plural, through),
+            // Unless the step divides the field, the gap from its last value to the first is shorter
+            _ if whole && self.counted && (self.max - self.min + 1).is_multiple_of(step) => counted,
+            _ if whole => ordinal,
+            _ if self.counted => format!("{} from {}", counted, through),
+            _ => format!("{} from {}", ordinal, through),
+        }
+    }
+}
+
+/// Values grouped into progressions of at least three, with the remaining values on their own.
+#[derive(Debug, PartialEq)]
+enum Piece {
+    Single(u16),
+    Progression { first: u16, last: u16, step: u16 },
+}
+
+/// Splits `values` into the fewest pieces by repeatedly taking the longest progression, in order of first value.
+fn pieces(values: &[u16]) -> Vec<Piece> {
+    let mut remaining: Vec<u16> = values.to_vec();
+    remaining.sort_unstable();
+    remaining.dedup();
+    let mut pieces = vec![];
+
+    loop {
+        let mut longest: Option<(u16, u16, usize)> = None;
+        for (index, &first) in remaining.iter().enumerate() {
+            for &second in &remaining[index + 1..] {
+                let step = second - first;
+                let length = (1..)
+                    .take_while(|&count| remaining.contains(&(first + step * count)))
+                    .count()
+                    + 1;
+                if longest.is_none_or(|(_, _, longest_length)| length > longest_length) {
+                    longest = Some((first, step, length));
+                }
+            }
+        }
+        match longest {
+            Some((first, step, length)) if length >= 3 => {
+                let last = first + step * (length as u16 - 1);
+                remaining.retain(|&value| value < first || value > last || (value - first) % step != 0);
+                pieces.push(Piece::Progression { first, last, step });
+            }
+            _ => break,
+        }
+    }
+
+    pieces.extend(remaining.into_iter().map(Piece::Single));
+    pieces.sort_by_key(|piece| match *piece {
+        Piece::Single(value) | Piece::Progression { first: value, .. } => value,
+    });
+    pieces
+}
+
+/// Joins phrases as in "a, b and c".
+pub fn list<T: AsRef<str>>(phrases: &[T]) -> String {
+    match phrases.split_last() {
+        None => String::new(),
+        Some((last, [])) => last.as_ref().to_string(),
+        Some((last, rest)) => {
+            let rest: Vec<&str> = rest.iter().map(AsRef::as_ref).collect();
+            format!("{} and {}", rest.join(", "), last.as_ref())
+        }
+    }
+}
+
+/// `phrase` starting with a capital letter.
+pub fn capitalize(phrase: &str) -> String {
+    let mut characters = phrase.chars();
+    match characters.next() {
+        Some(first) => first.to_uppercase().chain(characters).collect(),
+        None => String::new(),
+    }
+}
+
+/// The number followed by its English suffix, such as "2nd".
+pub fn ordinal(number: u16) -> String {
+    let suffix = match (number % 10, number % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", number, suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        capitalize, list, ordinal, pieces, Piece, DAYS_OF_WEEK, DAYS_OF_WEEK_FROM_MONDAY, HOURS, MINUTES, MONTHS,
+    };
+
+    #[test]
+    fn should_collapse_consecutive_values_into_range() {
+        assert_eq!(
+            pieces(&[3, 1, 2, 7]),
+            vec![
+                Piece::Progression {
+                    first: 1,
+                    last: 3,
+                    step: 1
+                },
+                Piece::Single(7)
+            ]
+        );
+    }
+
+    #[test]
+    fn should_collapse_stepped_values() {
+        assert_eq!(
+            pieces(&[0, 5, 7, 9, 1, 10, 15, 20, 25, 30, 35, 40, 45, 50, 55]),
+            vec![
+                Piece::Progression {
+                    first: 0,
+                    last: 55,
+                    step: 5
+                },
+                Piece::Single(1),
+                Piece::Single(7),
+                Piece::Single(9),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_describe_single_value() {
+        assert_eq!(MINUTES.describe(&[0]), "minute 0");
+    }
+
+    #[test]
+    fn should_describe_list() {
+        assert_eq!(HOURS.describe(&[17, 9]), "hours 9 and 17");
+    }
+
+    #[test]
+    fn should_describe_range() {
+        assert_eq!(MINUTES.describe(&[10, 11, 12, 13]), "minutes 10 through 13");
+    }
+
+    #[test]
+    fn should_describe_step_over_whole_field() {
+        assert_eq!(MINUTES.describe(&[0, 15, 30, 45]), "every 15 minutes");
+        assert_eq!(HOURS.describe(&[0, 6, 12, 18]), "every 6th hour");
+    }
+
+    #[test]
+    fn should_describe_step_which_does_not_divide_field_as_ordinal() {
+        assert_eq!(
+            MINUTES.describe(&[0, 7, 14, 21, 28, 35, 42, 49, 56]),
+            "every 7th minute"
+        );
+        assert_eq!(MINUTES.describe(&[0, 25, 50]), "every 25th minute");
+    }
+
+    #[test]
+    fn should_describe_step_within_range() {
+        assert_eq!(HOURS.describe(&[9, 11, 13, 15, 17]), "every 2nd hour from 9 through 17");
+    }
+
+    #[test]
+    fn should_describe_mixed_pieces() {
+        assert_eq!(
+            MINUTES.describe(&[0, 1, 5, 7, 9, 10, 15, 20, 25, 30, 35, 40, 45, 50, 55]),
+            "every 5 minutes and minutes 1, 7 and 9"
+        );
+    }
+
+    #[test]
+    fn should_describe_names() {
+        assert_eq!(MONTHS.describe(&[1, 7]), "January and July");
+        assert_eq!(DAYS_OF_WEEK.describe(&[1, 2, 3, 4, 5]), "Monday through Friday");
+        assert_eq!(DAYS_OF_WEEK_FROM_MONDAY.describe(&[6, 7]), "Saturday and Sunday");
+    }
+
+    #[test]
+    fn should_check_whole_field() {
+        assert!(DAYS_OF_WEEK.is_full(&[0, 1, 2, 3, 4, 5, 6]));
+        assert!(!MONTHS.is_full(&[1, 2]));
+    }
+
+    #[test]
+    fn should_join_phrases() {
+        assert_eq!(list(&["a"]), "a");
+        assert_eq!(list(&["a", "b", "c"]), "a, b and c");
+    }
+
+    #[test]
+    fn should_capitalize_phrases() {
+        assert_eq!(capitalize("every minute"), "Every minute");
+        assert_eq!(capitalize(""), "");
+    }
+
+    #[test]
+    fn should_get_ordinals() {
+        let ordinals: Vec<_> = [1, 2, 3, 4, 11, 12, 13, 21, 22]
+            .iter()
+            .map(|&number| ordinal(number))
+            .collect();
+        assert_eq!(
+            ordinals,
+            vec!["1st", "2nd", "3rd", "4th", "11th", "12th", "13th", "21st", "22nd"]
+        );
+    }
+}
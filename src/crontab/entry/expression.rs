@@ -0,0 +1,188 @@
+use std::collections::BTreeSet;
+
+/// `first` through `last` in steps of `step`, as one item of a list.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Item {
+    first: u16,
+    last: u16,
+    step: u16,
+}
+
+impl Item {
+    fn values(self) -> impl Iterator<Item = u16> {
+        (self.first..=self.last).step_by(usize::from(self.step))
+    }
+
+    /// The shortest way to write this, where `*` stands for `min` through `max` if `star`.
+    fn expression(self, min: u16, max: u16, star: bool) -> String {
+        if self.first == self.last {
+            return self.first.to_string();
+        }
+        if self.step == 1 {
+            return format!("{}-{}", self.first, self.last);
+        }
+        let to_end = self.last + self.step > max && self.last < max;
+        if star && self.first == min && (to_end || self.last == max) {
+            return format!("*/{}", self.step);
+        }
+        let exact = format!("{}-{}/{}", self.first, self.last, self.step);
+        let open = format!("{}-{}/{}", self.first, max, self.step);
+        if to_end && open.len() < exact.len() {
+            open
+        } else {
+            exact
+        }
+    }
+}
+
+/// The shortest list of numbers, ranges and steps with exactly `values`, which must be sorted and no less than `min`.
+/// Only with `star` may it use `*`, which stands for `min` through `max`.
+pub fn shortest(values: &[u16], min: u16, max: u16, star: bool) -> String {
+    if star && values.len() == usize::from(max - min) + 1 {
+        return "*".to_string();
+    }
+    let singles = values
+        .iter()
+        .map(|&value| Item {
+            first: value,
+            last: value,
+            step: 1,
+        })
+        .collect();
+    // Picking the biggest saving first can miss better ranges, so ranges alone are tried too
+    let candidates = vec![
+        join(singles, min, max, star),
+        join(cover(values, min, max, star, 1), min, max, star),
+        join(cover(values, min, max, star, max - min), min, max, star),
+    ];
+    candidates.into_iter().min_by_key(String::len).unwrap()
+}
+
+/// Like `shortest`, but starting with `*` or `*/step` so that a field still counts as unrestricted. That needs `min`
+/// among `values`, and without it this is the same as `shortest`.
+pub fn shortest_starred(values: &[u16], min: u16, max: u16) -> String {
+    if values.len() == usize::from(max - min) + 1 {
+        return "*".to_string();
+    }
+    let all: BTreeSet<u16> = values.iter().cloned().collect();
+    // The first value is always included, so the loop always finds the step past the end which includes only that
+    (2..=max - min + 1)
+        .filter(|&step| (min..=max).step_by(usize::from(step)).all(|value| all.contains(&value)))
+        .map(|step| {
+            let rest: Vec<u16> = values
+                .iter()
+                .cloned()
+                .filter(|value| !(value - min).is_multiple_of(step))
+                .collect();
+            if rest.is_empty() {
+                format!("*/{}", step)
+            } else {
+                format!("*/{},{}", step, shortest(&rest, min, max, true))
+            }
+        })
+        .min_by_key(String::len)
+        .unwrap_or_else(|| shortest(values, min, max, true))
+}
+
+/// Picks ranges and steps up to `max_step` one at a time by how many characters each saves over listing the values it
+/// adds, and lists the rest of `values` one by one.
+fn cover(values: &[u16], min: u16, max: u16, star: bool, max_step: u16) -> Vec<Item> {
+    let all: BTreeSet<u16> = values.iter().cloned().collect();
+    let mut uncovered = all.clone();
+    let mut items = vec![];
+    let listed_length = |value: u16| value.to_string().len() + 1;
+
+    loop {
+        let mut best: Option<(usize, Item)> = None;
+        for &first in values {
+            for step in 1..=max_step.max(1) {
+                // Only progressions which can't start any earlier
+                if first >= step && all.contains(&(first - step)) {
+                    continue;
+                }
+                let mut last = first;
+                while all.contains(&(last + step)) {
+                    last += step;
+                }
+                if last == first {
+                    continue;
+                }
+                let item = Item { first, last, step };
+                let added: usize = item
+                    .values()
+                    .filter(|value| uncovered.contains(value))
+                    .map(listed_length)
+                    .sum();
+                let cost = item.expression(min, max, star).len() + 1;
+                if added > cost && best.is_none_or(|(saving, _)| added - cost > saving) {
+                    best = Some((added - cost, item));
+                }
+            }
+        }
+        match best {
+            Some((_, item)) => {
+                for value in item.values() {
+                    uncovered.remove(&value);
+                }
+                items.push(item);
+            }
+            None => break,
+        }
+    }
+
+    items.extend(uncovered.into_iter().map(|value| Item {
+        first: value,
+        last: value,
+        step: 1,
+    }));
+    items.sort_by_key(|item| (item.first, item.step));
+    items
+}
+
+fn join(items: Vec<Item>, min: u16, max: u16, star: bool) -> String {
+    let expressions: Vec<String> = items.iter().map(|item| item.expression(min, max, star)).collect();
+    expressions.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{shortest, shortest_starred};
+
+    #[test]
+    fn should_write_every_value_as_star() {
+        let minutes: Vec<u16> = (0..60).collect();
+        assert_eq!(shortest(&minutes, 0, 59, true), "*");
+        assert_eq!(shortest(&minutes, 0, 59, false), "0-59");
+    }
+
+    #[test]
+    fn should_write_steps_and_ranges() {
+        assert_eq!(shortest(&[0, 15, 30, 45], 0, 59, true), "*/15");
+        assert_eq!(shortest(&[0, 15, 30, 45], 0, 59, false), "0-45/15");
+        assert_eq!(shortest(&[4, 19, 34, 49], 0, 59, true), "4-49/15");
+        assert_eq!(shortest(&[5, 20, 35, 50], 0, 59, true), "5-50/15");
+        assert_eq!(shortest(&[9, 10, 11, 12, 13, 14, 15, 16, 17], 0, 23, true), "9-17");
+        assert_eq!(shortest(&[1, 2, 3, 4, 5, 20], 0, 23, true), "1-5,20");
+        assert_eq!(shortest(&[0, 15, 30, 45, 50], 0, 59, true), "*/15,50");
+        assert_eq!(
+            shortest(&[9, 11, 20, 21, 22, 23, 24, 26, 27, 28, 29, 30], 1, 31, false),
+            "9,11,20-24,26-30"
+        );
+    }
+
+    #[test]
+    fn should_list_values_without_pattern() {
+        assert_eq!(shortest(&[5], 0, 59, true), "5");
+        assert_eq!(shortest(&[1, 2], 0, 59, true), "1,2");
+        assert_eq!(shortest(&[3, 7, 8, 30], 0, 59, true), "3,7,8,30");
+    }
+
+    #[test]
+    fn should_start_with_star_when_starred() {
+        let days: Vec<u16> = (1..32).collect();
+        assert_eq!(shortest_starred(&days, 1, 31), "*");
+        let odd_days: Vec<u16> = (1..32).step_by(2).collect();
+        assert_eq!(shortest_starred(&odd_days, 1, 31), "*/2");
+        assert_eq!(shortest_starred(&[1, 10, 15], 1, 31), "*/31,10,15");
+    }
+}
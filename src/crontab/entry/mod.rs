@@ -1,44 +1,223 @@
-use self::recurrence::Recurrence;
+pub use self::date_time_field_parser::DateTimeFieldParser;
+pub use self::recurrence::Recurrence;
+pub use self::trigger::Trigger;
+
+use chrono::{DateTime, TimeZone};
+use chrono_tz::Tz;
+use crontab::{CrontabKind, Dialect, Environment, ParseError, ParseOptions, Position};
+use std::borrow::Cow;
+use std::fmt;
+use std::path::Path;
 
 mod date_time_field_parser;
+mod day_rule;
+mod description;
+mod expression;
 mod recurrence;
 mod stepped_range;
+mod trigger;
+
+const REBOOT_NICKNAME: &str = "@reboot";
 
 pub struct Entry<'a> {
-    pub recurrence: Recurrence,
-    pub command: &'a str,
+    pub trigger: Trigger,
+    /// The time fields or nickname as written.
+    pub schedule: &'a str,
+    /// The time fields with each `H` replaced by the values it stands for, if there are any.
+    pub hashed_schedule: Option<String>,
+    /// The command cron runs, which is the text up to the first unescaped `%` with `\%` unescaped.
+    pub command: Cow<'a, str>,
+    /// What cron writes to the command's standard input, which is the text after the first unescaped `%` with
+    /// further unescaped `%` turned into newlines and `\%` unescaped.
+    pub stdin: Option<String>,
+    /// The command and standard input as written.
+    pub raw_command: &'a str,
+    /// The user the command runs as.
+    pub user: Cow<'a, str>,
+    /// The variables assigned before this entry in its crontab.
+    pub environment: Environment<'a>,
+    /// The zone the schedule is in, from `CRON_TZ` or `TZ`, if it's not the local one.
+    pub timezone: Option<Tz>,
+    /// The file the entry was read from, if any.
+    pub source: Option<&'a Path>,
+    /// One-based line number within its crontab, or zero when parsed on its own.
+    pub line: usize,
 }
 
 impl<'a> Entry<'a> {
-    pub fn new(entry: &str) -> Entry {
-        let fields = Entry::fields(entry);
+    /// Parses a single crontab line. Error spans are byte offsets within `entry`.
+    pub fn new(entry: &'a str) -> Result<Entry<'a>, ParseError> {
+        Entry::parse(entry, &ParseOptions::default())
+    }
+
+    pub fn parse(entry: &'a str, options: &ParseOptions) -> Result<Entry<'a>, ParseError> {
+        let fields = Entry::fields(entry, options.dialect)?;
+        let end_of_line = entry.trim_end().len();
+        let time_field_count = if fields[0] == REBOOT_NICKNAME {
+            1
+        } else {
+            options.dialect.field_count()
+        };
+
+        if fields.len() < time_field_count {
+            return Err(ParseError::TooFewFields {
+                found: fields.len(),
+                expected: options.dialect.field_count(),
+                position: Position::new(Entry::start_of(entry, fields[0])..end_of_line),
+            });
+        }
+        let missing_command = ParseError::MissingCommand {
+            position: Position::new(end_of_line..end_of_line),
+        };
+        let command = match fields.get(time_field_count) {
+            Some(&command) if !command.trim().is_empty() => command,
+            _ => return Err(missing_command),
+        };
+        let indentation = entry.len() - entry.trim_start().len();
+        let schedule = entry[indentation..Entry::start_of(entry, command)].trim_end();
+        let (user, command) = match options.kind {
+            CrontabKind::User => (Cow::Owned(options.owner()), command),
+            CrontabKind::System => match Entry::splitn_whitespace(command, 2)[..] {
+                [user, command] if !command.trim().is_empty() => (Cow::Borrowed(user), command),
+                _ => return Err(missing_command),
+            },
+        };
+
+        let seed = match options.host_id {
+            Some(ref host_id) => format!("{} {}", host_id, command),
+            None => command.to_string(),
+        };
+        let mut hashed_schedule = None;
+        let trigger = if fields[0] != REBOOT_NICKNAME {
+            let time_fields = &fields[0..time_field_count];
+            let starts: Vec<usize> = fields.iter().map(|field| Entry::start_of(entry, field)).collect();
+            let recurrence = Recurrence::new_at(time_fields, &starts, options.dialect, &seed)?;
+            if time_fields.iter().any(|field| DateTimeFieldParser::is_hashed(field)) {
+                let resolved: Vec<String> = DateTimeFieldParser::hashed_time_fields(options.dialect, &seed)
+                    .iter()
+                    .zip(time_fields)
+                    .map(|(parser, field)| parser.resolve_hashes(field))
+                    .collect();
+                hashed_schedule = Some(resolved.join(" "));
+            }
+            Trigger::Recurrence(recurrence)
+        } else {
+            Trigger::Reboot
+        };
+
+        let (parsed_command, stdin) = Entry::split_stdin(command);
+        Ok(Entry {
+            trigger,
+            schedule,
+            hashed_schedule,
+            command: parsed_command,
+            stdin,
+            raw_command: command,
+            user,
+            environment: Environment::default(),
+            timezone: None,
+            source: None,
+            line: 0,
+        })
+    }
+
+    /// The next time after `after` this runs, in the zone of `after`.
+    pub fn next_match<Zone: TimeZone>(&self, after: &DateTime<Zone>) -> Option<DateTime<Zone>> {
+        match self.timezone {
+            Some(ref timezone) => self
+                .trigger
+                .next_match(&after.with_timezone(timezone))
+                .map(|datetime| datetime.with_timezone(&after.timezone())),
+            None => self.trigger.next_match(after),
+        }
+    }
+
+    /// The last time before `before` this ran, in the zone of `before`.
+    pub fn previous_match<Zone: TimeZone>(&self, before: &DateTime<Zone>) -> Option<DateTime<Zone>> {
+        match self.timezone {
+            Some(ref timezone) => self
+                .trigger
+                .previous_match(&before.with_timezone(timezone))
+                .map(|datetime| datetime.with_timezone(&before.timezone())),
+            None => self.trigger.previous_match(before),
+        }
+    }
+
+    /// Splits `command` at the first unescaped `%` into the command and its standard input, the way cron does.
+    fn split_stdin(command: &'a str) -> (Cow<'a, str>, Option<String>) {
+        let mut parts = vec![String::new()];
+        let mut escaped = false;
+        for character in command.chars() {
+            let part = parts.last_mut().unwrap();
+            match character {
+                '%' if escaped => {
+                    part.pop();
+                    part.push('%');
+                }
+                '%' => parts.push(String::new()),
+                _ => part.push(character),
+            }
+            escaped = character == '\\' && !escaped;
+        }
 
-        Entry {
-            recurrence: Recurrence::new(&fields[0..5]),
-            command: fields[5],
+        let mut parts = parts.into_iter();
+        let parsed = parts.next().unwrap();
+        let parsed = parsed.trim_end();
+        let stdin: Vec<String> = parts.collect();
+        let stdin = if stdin.is_empty() { None } else { Some(stdin.join("\n")) };
+        if command.starts_with(parsed) {
+            (Cow::Borrowed(&command[..parsed.len()]), stdin)
+        } else {
+            (Cow::Owned(parsed.to_string()), stdin)
         }
     }
 
-    fn fields(entry: &'a str) -> Vec<&'a str> {
-        let trimmed = entry.trim_left();
-        match trimmed.chars().next().unwrap() {
-            '@' => Entry::split_with_datetime_nickname(trimmed),
-            _ => Entry::splitn_whitespace(trimmed, 6),
+    fn fields(entry: &'a str, dialect: Dialect) -> Result<Vec<&'a str>, ParseError> {
+        let trimmed = entry.trim_start();
+        match trimmed.chars().next() {
+            Some('@') => Entry::split_with_datetime_nickname(entry, dialect),
+            _ => Ok(Entry::splitn_whitespace(trimmed, dialect.field_count() + 1)),
         }
     }
 
-    fn split_with_datetime_nickname(entry: &str) -> Vec<&str> {
-        let split = Entry::splitn_whitespace(entry, 2);
+    fn split_with_datetime_nickname(entry: &str, dialect: Dialect) -> Result<Vec<&str>, ParseError> {
+        let split = Entry::splitn_whitespace(entry.trim_start(), 2);
         let mut fields = match split[0] {
             "@yearly" | "@annually" => vec!["0", "0", "1", "1", "*"],
             "@monthly" => vec!["0", "0", "1", "*", "*"],
-            "@weekly" => vec!["0", "0", "*", "*", "0"],
-            "@daily" => vec!["0", "0", "*", "*", "*"],
+            "@weekly" => vec!["0", "0", "*", "*", "SUN"],
+            "@daily" | "@midnight" => vec!["0", "0", "*", "*", "*"],
             "@hourly" => vec!["0", "*", "*", "*", "*"],
-            value => panic!("Unhandled datetime nickname ‘{}’", value),
+            REBOOT_NICKNAME => vec![REBOOT_NICKNAME],
+            value => {
+                let start = Entry::start_of(entry, value);
+                return Err(ParseError::UnknownNickname {
+                    nickname: value.to_string(),
+                    position: Position::new(start..start + value.len()),
+                });
+            }
         };
-        fields.push(split[1]);
-        fields
+        if fields[0] != REBOOT_NICKNAME {
+            if dialect.has_seconds() {
+                fields.insert(0, "0");
+            }
+            if dialect.has_year() {
+                fields.push("*");
+            }
+        }
+        fields.extend(split.get(1));
+        Ok(fields)
+    }
+
+    /// Byte offset of `field` within `entry`. Fields expanded from a datetime nickname aren't part of `entry`,
+    /// so they are reported at the start of the nickname.
+    fn start_of(entry: &str, field: &str) -> usize {
+        let offset = (field.as_ptr() as usize).wrapping_sub(entry.as_ptr() as usize);
+        if offset <= entry.len() {
+            offset
+        } else {
+            entry.len() - entry.trim_start().len()
+        }
     }
 
     fn splitn_whitespace(entry: &str, max_entries: usize) -> Vec<&str> {
@@ -56,72 +235,257 @@ impl<'a> Entry<'a> {
                     false
                 }
             })
-            .map(str::trim_left)
+            .map(str::trim_start)
             .collect()
     }
 }
 
+impl<'a> fmt::Display for Entry<'a> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{}: {}", self.trigger, self.command)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Entry;
+    use super::{Entry, Trigger};
+    use crontab::{Dialect, ParseError, ParseOptions, Position};
+    use test_support::user_options;
 
     #[test]
     fn should_create_entry_with_command() {
-        let actual = Entry::new("1 2 3 4 5 command");
+        let actual = Entry::new("1 2 3 4 5 command").unwrap();
         assert_eq!(actual.command, "command");
     }
 
     #[test]
     fn should_split_into_six_fields() {
-        let actual = Entry::fields("  1  2   3 4 5   command  with   spaces  ");
+        let actual = Entry::fields("  1  2   3 4 5   command  with   spaces  ", Dialect::default()).unwrap();
         assert_eq!(actual, vec!["1", "2", "3", "4", "5", "command  with   spaces  "]);
     }
 
     #[test]
     fn should_handle_split_entry_with_nickname_into_six_fields() {
-        let actual = Entry::fields("@yearly   command   with   spaces");
+        let actual = Entry::fields("@yearly   command   with   spaces", Dialect::default()).unwrap();
         assert_eq!(actual, vec!["0", "0", "1", "1", "*", "command   with   spaces"]);
     }
 
     #[test]
     fn should_handle_yearly_nickname() {
-        let actual = Entry::fields("@yearly command");
+        let actual = Entry::fields("@yearly command", Dialect::default()).unwrap();
         assert_eq!(actual, vec!["0", "0", "1", "1", "*", "command"]);
     }
 
     #[test]
     fn should_handle_annually_nickname() {
-        let actual = Entry::fields("@annually command");
+        let actual = Entry::fields("@annually command", Dialect::default()).unwrap();
         assert_eq!(actual, vec!["0", "0", "1", "1", "*", "command"]);
     }
 
     #[test]
     fn should_handle_monthly_nickname() {
-        let actual = Entry::fields("@monthly command");
+        let actual = Entry::fields("@monthly command", Dialect::default()).unwrap();
         assert_eq!(actual, vec!["0", "0", "1", "*", "*", "command"]);
     }
 
     #[test]
     fn should_handle_weekly_nickname() {
-        let actual = Entry::fields("@weekly command");
-        assert_eq!(actual, vec!["0", "0", "*", "*", "0", "command"]);
+        let actual = Entry::fields("@weekly command", Dialect::default()).unwrap();
+        assert_eq!(actual, vec!["0", "0", "*", "*", "SUN", "command"]);
     }
 
     #[test]
     fn should_handle_daily_nickname() {
-        let actual = Entry::fields("@daily command");
+        let actual = Entry::fields("@daily command", Dialect::default()).unwrap();
         assert_eq!(actual, vec!["0", "0", "*", "*", "*", "command"]);
     }
 
     #[test]
     fn should_handle_hourly_nickname() {
-        let actual = Entry::fields("@hourly command");
+        let actual = Entry::fields("@hourly command", Dialect::default()).unwrap();
         assert_eq!(actual, vec!["0", "*", "*", "*", "*", "command"]);
     }
 
     #[test]
-    #[should_panic(expected = "Unhandled datetime nickname ‘@reboot’")]
-    fn should_fail_on_reboot_nickname() {
-        Entry::fields("@reboot command");
+    fn should_handle_midnight_nickname() {
+        let actual = Entry::fields("@midnight command", Dialect::default()).unwrap();
+        assert_eq!(actual, vec!["0", "0", "*", "*", "*", "command"]);
+    }
+
+    #[test]
+    fn should_keep_reboot_nickname_as_only_time_field() {
+        let actual = Entry::fields("@reboot command  with spaces", Dialect::default()).unwrap();
+        assert_eq!(actual, vec!["@reboot", "command  with spaces"]);
+    }
+
+    #[test]
+    fn should_keep_schedule_as_written() {
+        let actual = Entry::new("  */15  9-17 * * mon-fri   command").unwrap();
+        assert_eq!(actual.schedule, "*/15  9-17 * * mon-fri");
+    }
+
+    #[test]
+    fn should_keep_nickname_as_schedule() {
+        let actual = Entry::new("@daily command").unwrap();
+        assert_eq!(actual.schedule, "@daily");
+    }
+
+    #[test]
+    fn should_display_description_and_command() {
+        let actual = Entry::new("30 6 * * 1-5 backup --all").unwrap();
+        assert_eq!(actual.to_string(), "At 06:30, Monday through Friday: backup --all");
+    }
+
+    #[test]
+    fn should_create_reboot_entry() {
+        let actual = Entry::new("@reboot command").unwrap();
+        assert_eq!(actual.trigger, Trigger::Reboot);
+        assert_eq!(actual.command, "command");
+    }
+
+    #[test]
+    fn should_report_missing_command_after_reboot_nickname() {
+        assert!(matches!(
+            Entry::new("@reboot  "),
+            Err(ParseError::MissingCommand { .. })
+        ));
+    }
+
+    #[test]
+    fn should_fail_on_unknown_nickname() {
+        assert_eq!(
+            Entry::fields("@fortnightly command", Dialect::default()).err(),
+            Some(ParseError::UnknownNickname {
+                nickname: "@fortnightly".to_string(),
+                position: Position::new(0..12),
+            })
+        );
+    }
+
+    #[test]
+    fn should_report_unknown_nickname_after_indentation() {
+        let error = Entry::new("  @fortnightly command").err().unwrap();
+        assert_eq!(error.position().span, 2..14);
+    }
+
+    #[test]
+    fn should_report_too_few_fields() {
+        assert_eq!(
+            Entry::new(" 1 2 3").err(),
+            Some(ParseError::TooFewFields {
+                found: 3,
+                expected: 5,
+                position: Position::new(1..6),
+            })
+        );
+    }
+
+    #[test]
+    fn should_report_missing_command() {
+        assert_eq!(
+            Entry::new("1 2 3 4 5  ").err(),
+            Some(ParseError::MissingCommand {
+                position: Position::new(9..9),
+            })
+        );
+    }
+
+    #[test]
+    fn should_report_missing_command_after_nickname() {
+        assert!(matches!(Entry::new("@daily"), Err(ParseError::MissingCommand { .. })));
+    }
+
+    #[test]
+    fn should_report_field_error_at_line_offset() {
+        let error = Entry::new("1  2 3 4 x5 command").err().unwrap();
+        assert_eq!(
+            error,
+            ParseError::BadNumber {
+                value: "x5".to_string(),
+                position: Position::new(9..11),
+            }
+        );
+    }
+
+    #[test]
+    fn should_parse_quartz_entry_with_seconds_and_year() {
+        let options = ParseOptions {
+            dialect: Dialect::Quartz,
+            ..ParseOptions::default()
+        };
+        let actual = Entry::parse("0 0 12 ? * MON-FRI 2026 command", &options).unwrap();
+        assert_eq!(actual.schedule, "0 0 12 ? * MON-FRI 2026");
+        assert_eq!(actual.command, "command");
+    }
+
+    #[test]
+    fn should_expand_nickname_to_fields_of_dialect() {
+        let actual = Entry::fields("@daily command", Dialect::Quartz).unwrap();
+        assert_eq!(actual, vec!["0", "0", "0", "*", "*", "*", "*", "command"]);
+    }
+
+    #[test]
+    fn should_hash_schedule_by_command_and_host() {
+        let options = |host_id: &str| ParseOptions {
+            host_id: Some(host_id.to_string()),
+            ..user_options()
+        };
+        let web = Entry::parse("H H * * * backup", &options("web-1")).unwrap();
+        assert_eq!(
+            web.hashed_schedule,
+            Entry::parse("H H * * * backup", &options("web-1"))
+                .unwrap()
+                .hashed_schedule
+        );
+        assert_ne!(
+            web.hashed_schedule,
+            Entry::parse("H H * * * backup", &options("web-2"))
+                .unwrap()
+                .hashed_schedule
+        );
+        assert_ne!(
+            web.hashed_schedule,
+            Entry::parse("H H * * * restore", &options("web-1"))
+                .unwrap()
+                .hashed_schedule
+        );
+        assert_eq!(
+            Entry::parse("0 * * * * backup", &options("web-1"))
+                .unwrap()
+                .hashed_schedule,
+            None
+        );
+    }
+
+    #[test]
+    fn should_not_take_day_names_for_hashes() {
+        let options = user_options();
+        assert_eq!(
+            Entry::parse("0 9 * * THU weekly", &options).unwrap().hashed_schedule,
+            None
+        );
+        assert_eq!(
+            Entry::parse("0 9 * * THU,H(1-5) weekly", &options)
+                .unwrap()
+                .hashed_schedule
+                .map(|schedule| schedule.starts_with("0 9 * * THU,")),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn should_unescape_percent_in_command() {
+        let actual = Entry::new(r"1 2 3 4 5 date +\%Y").unwrap();
+        assert_eq!(actual.command, "date +%Y");
+        assert_eq!(actual.stdin, None);
+        assert_eq!(actual.raw_command, r"date +\%Y");
+    }
+
+    #[test]
+    fn should_split_standard_input_at_unescaped_percent() {
+        let actual = Entry::new("1 2 3 4 5 mail -s report root % Dear root,%%All done").unwrap();
+        assert_eq!(actual.command, "mail -s report root");
+        assert_eq!(actual.stdin, Some(" Dear root,\n\nAll done".to_string()));
+        assert_eq!(actual.raw_command, "mail -s report root % Dear root,%%All done");
     }
 }
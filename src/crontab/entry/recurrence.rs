@@ -1,202 +1,1513 @@
 use super::date_time_field_parser::DateTimeFieldParser;
+use super::day_rule::DayRule;
+use super::description::{
+    self, DAYS_OF_MONTH, DAYS_OF_WEEK, DAYS_OF_WEEK_FROM_MONDAY, HOURS, MINUTES, MONTHS, SECONDS, YEARS,
+};
 use chrono::prelude::*;
-use time::Duration;
+use chrono::{Duration, LocalResult};
+use crontab::{Dialect, ParseError};
+use std::convert::TryFrom;
+use std::fmt;
 
+/// Times of day which are listed one by one rather than described per field.
+const MAX_LISTED_TIMES: usize = 4;
+/// The longest each month can be, in a leap year.
+const DAYS_IN_MONTH: [u8; 12] = [31, 29, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+/// Bits 0, 7, 14, 21 and 28, for the days of a month which fall on the same day of the week as the first.
+const WEEKLY: u64 = 0x1020_4081;
+/// The first of 28 years with every combination of leap year and day of the week the year starts on.
+const LEAP_YEAR_CYCLE_START: i32 = 2001;
+/// Days of the week in iCalendar, starting from Sunday like cron.
+const RRULE_WEEKDAYS: [&str; 7] = ["SU", "MO", "TU", "WE", "TH", "FR", "SA"];
+
+/// Bit N of each field is set if the field includes the value N.
 #[derive(Debug, PartialEq)]
 pub struct Recurrence {
-    minutes: Vec<u8>,
-    hours: Vec<u8>,
-    days_of_month: Vec<u8>,
-    months: Vec<u8>,
-    days_of_week: Vec<u8>,
+    /// Only second 0 unless the dialect has a seconds field.
+    seconds: u64,
+    minutes: u64,
+    hours: u32,
+    days_of_month: u32,
+    months: u32,
+    days_of_week: u32,
+    /// The years this runs in, in order, or `None` for every year.
+    years: Option<Vec<u16>>,
+    /// Days of either day field which depend on the month.
+    day_rules: Vec<DayRule>,
+    days_of_month_wildcard: bool,
+    days_of_week_wildcard: bool,
+    /// Whether the minute or hour field starts with `*`, which cronie runs by the new clock after a clock change.
+    time_wildcard: bool,
+    dialect: Dialect,
 }
 
 impl Recurrence {
-    pub fn new(fields: &[&str]) -> Recurrence {
-        let minutes_parser: DateTimeFieldParser = DateTimeFieldParser::new(0, 59);
-        let hours_parser: DateTimeFieldParser = DateTimeFieldParser::new(0, 23);
-        let days_of_month_parser: DateTimeFieldParser = DateTimeFieldParser::new(1, 31);
-        let months_parser: DateTimeFieldParser = DateTimeFieldParser::new(1, 12);
-        let days_of_week_parser: DateTimeFieldParser = DateTimeFieldParser::new_with_wrap_around(0, 6);
-
-        Recurrence {
-            minutes: minutes_parser.parse_field(fields[0]),
-            hours: hours_parser.parse_field(fields[1]),
-            days_of_month: days_of_month_parser.parse_field(fields[2]),
-            months: months_parser.parse_field(fields[3]),
-            days_of_week: days_of_week_parser.parse_field(fields[4]),
-        }
-    }
-
-    pub fn next_match(&self, after: NaiveDateTime) -> NaiveDateTime {
-        let next_minute = NextPeriod::new(&(after.minute() as u8 + 1), &self.minutes);
-        let next_hour = NextPeriod::new(&(after.hour() as u8 + next_minute.overflow as u8), &self.hours);
-        let next_time_of_day = NaiveTime::from_hms(u32::from(next_hour.period), u32::from(next_minute.period), 0);
-        let mut current = after.date().and_time(next_time_of_day) + Duration::days(i64::from(next_hour.overflow));
-
-        while !self.matches(current) {
-            current += Duration::days(1);
-        }
-        current
-    }
-
-    fn matches(&self, instant: NaiveDateTime) -> bool {
-        let minute = &(instant.minute() as u8);
-        let hour = &(instant.hour() as u8);
-        let day_of_month = &(instant.day() as u8);
-        let month = &(instant.month() as u8);
-        let day_of_week = &(instant.weekday() as u8);
-        self.minutes.contains(minute)
-            && self.hours.contains(hour)
-            && self.days_of_month.contains(day_of_month)
-            && self.months.contains(month)
-            && self.days_of_week.contains(day_of_week)
+    /// Parses the five time fields, with `H` hashed from an empty seed. Error spans count from the start of the fields
+    /// joined by single spaces.
+    pub fn new(fields: &[&str]) -> Result<Recurrence, ParseError> {
+        let mut starts = Vec::with_capacity(fields.len());
+        let mut start = 0;
+        for field in fields {
+            starts.push(start);
+            start += field.len() + 1;
+        }
+        Recurrence::new_at(fields, &starts, Dialect::default(), "")
     }
-}
 
-struct NextPeriod {
-    period: u8,
-    overflow: u8,
-}
+    /// Parses the time fields of `dialect`, with `H` hashed from `seed`, shifting error spans by the byte offset each
+    /// field starts at.
+    pub fn new_at(fields: &[&str], starts: &[usize], dialect: Dialect, seed: &str) -> Result<Recurrence, ParseError> {
+        let parsers = DateTimeFieldParser::hashed_time_fields(dialect, seed);
+        let parse = |index: usize| {
+            parsers[index]
+                .parse_field(fields[index])
+                .map_err(|error| error.shifted(starts[index]))
+        };
+        let bits = |index: usize| parse(index).map(|values| values.iter().fold(0u64, |bits, &value| bits | 1 << value));
+        let days = |index: usize| {
+            parsers[index]
+                .parse_day_field(fields[index])
+                .map(|(values, rules)| (values.iter().fold(0u32, |bits, &value| bits | 1 << value), rules))
+                .map_err(|error| error.shifted(starts[index]))
+        };
+        let unrestricted = |index: usize| fields[index].starts_with('*') || fields[index] == "?";
+
+        // Index of the minutes field
+        let first = if dialect.has_seconds() { 1 } else { 0 };
+        let (days_of_month, mut day_rules) = days(first + 2)?;
+        let (days_of_week, day_of_week_rules) = days(first + 4)?;
+        day_rules.extend(day_of_week_rules);
+        Ok(Recurrence {
+            seconds: if dialect.has_seconds() { bits(0)? } else { 1 },
+            minutes: bits(first)?,
+            hours: bits(first + 1)? as u32,
+            days_of_month,
+            months: bits(first + 3)? as u32,
+            days_of_week,
+            years: if dialect.has_year() && !unrestricted(first + 5) {
+                Some(parse(first + 5)?)
+            } else {
+                None
+            },
+            day_rules,
+            days_of_month_wildcard: unrestricted(first + 2),
+            days_of_week_wildcard: unrestricted(first + 4),
+            time_wildcard: unrestricted(first) || unrestricted(first + 1),
+            dialect,
+        })
+    }
+
+    /// The next second after `after` this runs at, or `None` if it never runs again.
+    pub fn next_match(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        if !self.is_satisfiable() {
+            return None;
+        }
+        let (mut year, mut month, mut day) = (after.year(), after.month(), after.day());
+        let (mut hour, mut minute, mut second) = (after.hour(), after.minute(), after.second() + 1);
+
+        // Jump to the next year, month, day, hour, minute and second in turn, starting the smaller fields over
+        // whenever a larger one moves on. Some date matches, so this ends, if only after decades for February 29th
+        // on a given day of the week.
+        loop {
+            if let Some(ref years) = self.years {
+                match years.iter().find(|&&next| i32::from(next) >= year) {
+                    Some(&next) if i32::from(next) == year => {}
+                    Some(&next) => {
+                        year = i32::from(next);
+                        month = 1;
+                        day = 1;
+                        hour = 0;
+                        minute = 0;
+                        second = 0;
+                    }
+                    None => return None,
+                }
+            }
+            match next_value(self.months, month) {
+                Some(next) if next == month => {}
+                Some(next) => {
+                    month = next;
+                    day = 1;
+                    hour = 0;
+                    minute = 0;
+                    second = 0;
+                }
+                None => {
+                    year += 1;
+                    month = 1;
+                    day = 1;
+                    hour = 0;
+                    minute = 0;
+                    second = 0;
+                    continue;
+                }
+            }
+            match next_value(self.days_in(year, month)?, day) {
+                Some(next) if next == day => {}
+                Some(next) => {
+                    day = next;
+                    hour = 0;
+                    minute = 0;
+                    second = 0;
+                }
+                None => {
+                    month += 1;
+                    day = 1;
+                    hour = 0;
+                    minute = 0;
+                    second = 0;
+                    continue;
+                }
+            }
+            match next_value(self.hours, hour) {
+                Some(next) if next == hour => {}
+                Some(next) => {
+                    hour = next;
+                    minute = 0;
+                    second = 0;
+                }
+                None => {
+                    day += 1;
+                    hour = 0;
+                    minute = 0;
+                    second = 0;
+                    continue;
+                }
+            }
+            match next_value(self.minutes, minute) {
+                Some(next) if next == minute => {}
+                Some(next) => {
+                    minute = next;
+                    second = 0;
+                }
+                None => {
+                    hour += 1;
+                    minute = 0;
+                    second = 0;
+                    continue;
+                }
+            }
+            match next_value(self.seconds, second) {
+                Some(next) => return NaiveDate::from_ymd_opt(year, month, day)?.and_hms_opt(hour, minute, next),
+                None => {
+                    minute += 1;
+                    second = 0;
+                }
+            }
+        }
+    }
+
+    /// The last second before `before` this ran at, or `None` if it never ran.
+    pub fn previous_match(&self, before: NaiveDateTime) -> Option<NaiveDateTime> {
+        if !self.is_satisfiable() {
+            return None;
+        }
+        let (mut year, mut month, mut day) = (before.year(), before.month() as i32, before.day() as i32);
+        let (mut hour, mut minute, mut second) =
+            (before.hour() as i32, before.minute() as i32, before.second() as i32 - 1);
+
+        // The same jumps as `next_match` in the other direction, starting the smaller fields over from their ends.
+        // Counting down past the first value of a field moves on to the larger field.
+        loop {
+            if let Some(ref years) = self.years {
+                match years.iter().rev().find(|&&previous| i32::from(previous) <= year) {
+                    Some(&previous) if i32::from(previous) == year => {}
+                    Some(&previous) => {
+                        year = i32::from(previous);
+                        month = 12;
+                        day = 31;
+                        hour = 23;
+                        minute = 59;
+                        second = 59;
+                    }
+                    None => return None,
+                }
+            }
+            match previous_value(self.months, month) {
+                Some(previous) if previous == month => {}
+                Some(previous) => {
+                    month = previous;
+                    day = 31;
+                    hour = 23;
+                    minute = 59;
+                    second = 59;
+                }
+                None => {
+                    year -= 1;
+                    month = 12;
+                    day = 31;
+                    hour = 23;
+                    minute = 59;
+                    second = 59;
+                    continue;
+                }
+            }
+            match previous_value(self.days_in(year, month as u32)?, day) {
+                Some(previous) if previous == day => {}
+                Some(previous) => {
+                    day = previous;
+                    hour = 23;
+                    minute = 59;
+                    second = 59;
+                }
+                None => {
+                    month -= 1;
+                    day = 31;
+                    hour = 23;
+                    minute = 59;
+                    second = 59;
+                    continue;
+                }
+            }
+            match previous_value(self.hours, hour) {
+                Some(previous) if previous == hour => {}
+                Some(previous) => {
+                    hour = previous;
+                    minute = 59;
+                    second = 59;
+                }
+                None => {
+                    day -= 1;
+                    hour = 23;
+                    minute = 59;
+                    second = 59;
+                    continue;
+                }
+            }
+            match previous_value(self.minutes, minute) {
+                Some(previous) if previous == minute => {}
+                Some(previous) => {
+                    minute = previous;
+                    second = 59;
+                }
+                None => {
+                    hour -= 1;
+                    minute = 59;
+                    second = 59;
+                    continue;
+                }
+            }
+            match previous_value(self.seconds, second) {
+                Some(previous) => {
+                    return NaiveDate::from_ymd_opt(year, month as u32, day as u32)?.and_hms_opt(
+                        hour as u32,
+                        minute as u32,
+                        previous as u32,
+                    )
+                }
+                None => {
+                    minute -= 1;
+                    second = 59;
+                }
+            }
+        }
+    }
+
+    /// The days of `month` this runs on, with bit N set for day N, or `None` if the year is out of range.
+    fn days_in(&self, year: i32, month: u32) -> Option<u64> {
+        let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+        let length = if month == 2 && NaiveDate::from_ymd_opt(year, 2, 29).is_none() {
+            28
+        } else {
+            u32::from(DAYS_IN_MONTH[month as usize - 1])
+        };
+        let first_weekday = first.weekday().num_days_from_sunday();
+        let mut days_of_month = u64::from(self.days_of_month);
+        let mut days_of_week = (0..7)
+            .filter(|&weekday| contains(self.days_of_week, weekday))
+            .fold(0, |days, weekday| {
+                days | WEEKLY << (1 + (weekday + 7 - first_weekday) % 7)
+            });
+        for rule in &self.day_rules {
+            if let Some(day) = rule.day_in(first_weekday, length) {
+                if rule.is_day_of_week() {
+                    days_of_week |= 1 << day;
+                } else {
+                    days_of_month |= 1 << day;
+                }
+            }
+        }
+        let days = if self.either_day() {
+            days_of_month | days_of_week
+        } else {
+            days_of_month & days_of_week
+        };
+        Some(days & ((1 << (length + 1)) - 2))
+    }
+
+    /// The next match after `after` in its time zone, or `None` if it never runs. This follows cronie across clock
+    /// changes: if the minute and hour are both fixed, times skipped by a forward change run once right after it, and
+    /// times repeated by a backward change only run the first time. Otherwise this runs by the new clock, skipping the
+    /// skipped times and running the repeated ones again.
+    pub fn next_match_in<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let zone = after.timezone();
+        let mut current = after.naive_local();
+        if self.time_wildcard {
+            if let LocalResult::Ambiguous(earliest, latest) = zone.from_local_datetime(&current) {
+                // Past the first run of the repeated times, they come again
+                if earliest == *after {
+                    let next = self.next_match(current)?;
+                    if let LocalResult::Ambiguous(earliest, _) = zone.from_local_datetime(&next) {
+                        return Some(earliest);
+                    }
+                    current -= latest.naive_utc() - earliest.naive_utc();
+                }
+            }
+        }
+        loop {
+            current = self.next_match(current)?;
+            let candidate = match zone.from_local_datetime(&current) {
+                LocalResult::Single(datetime) => datetime,
+                LocalResult::Ambiguous(earliest, latest) if self.time_wildcard => {
+                    if earliest > *after {
+                        earliest
+                    } else {
+                        latest
+                    }
+                }
+                LocalResult::Ambiguous(earliest, _) => earliest,
+                LocalResult::None if self.time_wildcard => continue,
+                LocalResult::None => Recurrence::end_of_gap(&zone, current),
+            };
+            if candidate > *after {
+                return Some(candidate);
+            }
+        }
+    }
+
+    /// The last match before `before` in its time zone, or `None` if it never runs. Clock changes are handled like in
+    /// `next_match_in`, so this lists the same runs in reverse.
+    pub fn previous_match_in<Tz: TimeZone>(&self, before: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let zone = before.timezone();
+        let mut current = before.naive_local();
+        if self.time_wildcard {
+            if let LocalResult::Ambiguous(earliest, latest) = zone.from_local_datetime(&current) {
+                // Before the second run of the repeated times, they came once already
+                if latest == *before {
+                    let previous = self.previous_match(current)?;
+                    if let LocalResult::Ambiguous(_, latest) = zone.from_local_datetime(&previous) {
+                        return Some(latest);
+                    }
+                    current += latest.naive_utc() - earliest.naive_utc();
+                }
+            }
+        }
+        loop {
+            current = self.previous_match(current)?;
+            let candidate = match zone.from_local_datetime(&current) {
+                LocalResult::Single(datetime) => datetime,
+                LocalResult::Ambiguous(earliest, latest) if self.time_wildcard => {
+                    if latest < *before {
+                        latest
+                    } else {
+                        earliest
+                    }
+                }
+                LocalResult::Ambiguous(earliest, _) => earliest,
+                LocalResult::None if self.time_wildcard => continue,
+                LocalResult::None => Recurrence::end_of_gap(&zone, current),
+            };
+            if candidate < *before {
+                return Some(candidate);
+            }
+        }
+    }
+
+    /// The first existing time after `local`, which was skipped by a forward clock change.
+    fn end_of_gap<Tz: TimeZone>(zone: &Tz, local: NaiveDateTime) -> DateTime<Tz> {
+        // Clocks change on the minute
+        let mut local = local - Duration::seconds(i64::from(local.second()));
+        loop {
+            local += Duration::minutes(1);
+            if let Some(datetime) = zone.from_local_datetime(&local).earliest() {
+                return datetime;
+            }
+        }
+    }
+
+    /// The shortest expression with the same schedule, written with numbers, ranges, steps and `*`. In dialects with
+    /// seconds an unrestricted day field is written `?` if the other one is restricted, or for days of the week.
+    pub fn to_expression(&self) -> String {
+        let parsers = DateTimeFieldParser::time_fields(self.dialect);
+        let first = if self.dialect.has_seconds() { 1 } else { 0 };
+        let rules = |day_of_week: bool| -> Vec<DayRule> {
+            self.day_rules
+                .iter()
+                .copied()
+                .filter(|rule| rule.is_day_of_week() == day_of_week)
+                .collect()
+        };
+        let plain = |index: usize, values: Vec<u16>| parsers[index].expression(&values, &[], None);
+
+        let mut fields = vec![];
+        if self.dialect.has_seconds() {
+            fields.push(plain(0, values(self.seconds)));
+        }
+        fields.extend(self.time_expressions(&parsers[first..first + 2]));
+        fields.push(parsers[first + 2].expression(
+            &values(self.days_of_month),
+            &rules(false),
+            Some(self.days_of_month_wildcard),
+        ));
+        fields.push(plain(first + 3, values(self.months)));
+        fields.push(parsers[first + 4].expression(
+            &values(self.days_of_week),
+            &rules(true),
+            Some(self.days_of_week_wildcard),
+        ));
+        if self.dialect.has_seconds() {
+            if fields[first + 4] == "*" {
+                fields[first + 4] = "?".to_string();
+            } else if fields[first + 2] == "*" {
+                fields[first + 2] = "?".to_string();
+            }
+        }
+        if self.dialect.has_year() {
+            fields.push(match self.years {
+                Some(ref years) => parsers[first + 5].expression(years, &[], Some(false)),
+                None => "*".to_string(),
+            });
+        }
+        fields.join(" ")
+    }
+
+    /// The shortest minute and hour fields which start with `*` in either exactly when the written ones did, since
+    /// that changes how clock changes are handled.
+    fn time_expressions(&self, parsers: &[DateTimeFieldParser]) -> Vec<String> {
+        let expression = |index: usize, wildcard: Option<bool>| {
+            let bits = if index == 0 {
+                self.minutes
+            } else {
+                u64::from(self.hours)
+            };
+            parsers[index].expression(&values(bits), &[], wildcard)
+        };
+        if !self.time_wildcard {
+            return vec![expression(0, Some(false)), expression(1, Some(false))];
+        }
+        let (minutes, hours) = (expression(0, None), expression(1, None));
+        if minutes.starts_with('*') || hours.starts_with('*') {
+            return vec![minutes, hours];
+        }
+        // Only a field which includes its first value can start with `*`, and at least one of them does
+        let starred = |index: usize| Some(expression(index, Some(true))).filter(|field| field.starts_with('*'));
+        let candidates = vec![
+            starred(0).map(|starred_minutes| vec![starred_minutes, hours.clone()]),
+            starred(1).map(|starred_hours| vec![minutes.clone(), starred_hours]),
+        ];
+        candidates
+            .into_iter()
+            .flatten()
+            .min_by_key(|fields| fields[0].len() + fields[1].len())
+            .unwrap_or_else(|| vec![minutes, hours])
+    }
+
+    /// An English description, such as "At minute 0 past every 2nd hour from 9 through 17, Monday through Friday, in
+    /// January and July".
+    pub fn describe(&self) -> String {
+        let mut description = self.describe_time();
+        if let Some(days) = self.describe_days() {
+            description.push_str(", ");
+            description.push_str(&days);
+        }
+        let months = values(self.months);
+        if !MONTHS.is_full(&months) {
+            description.push_str(&format!(", in {}", MONTHS.describe(&months)));
+        }
+        if let Some(ref years) = self.years {
+            description.push_str(&format!(", in {}", YEARS.describe(years)));
+        }
+        description
+    }
+
+    /// Describes the time fields by the values in them rather than as written, so that equivalent fields such as
+    /// `*/15`, `0-59/15` and `0,15,30,45` read the same way and overlapping list items are merged.
+    fn describe_time(&self) -> String {
+        let seconds = values(self.seconds);
+        let minutes = values(self.minutes);
+        let hours = values(self.hours);
+        let on_the_minute = seconds == [0];
+        let all_minutes = MINUTES.is_full(&minutes);
+        let all_hours = HOURS.is_full(&hours);
+        if all_minutes && all_hours && on_the_minute {
+            return "Every minute".to_string();
+        }
+
+        let singles = |values: &[u16], unit: &description::Unit| unit.complexity(values) == values.len();
+        if singles(&seconds, &SECONDS)
+            && singles(&minutes, &MINUTES)
+            && singles(&hours, &HOURS)
+            && seconds.len() * minutes.len() * hours.len() <= MAX_LISTED_TIMES
+        {
+            let mut times = vec![];
+            for hour in &hours {
+                for minute in &minutes {
+                    for second in &seconds {
+                        times.push(if on_the_minute {
+                            format!("{:02}:{:02}", hour, minute)
+                        } else {
+                            format!("{:02}:{:02}:{:02}", hour, minute, second)
+                        });
+                    }
+                }
+            }
+            return format!("At {}", description::list(&times));
+        }
+
+        let described_minutes = if all_minutes {
+            "every minute".to_string()
+        } else {
+            at(MINUTES.describe(&minutes))
+        };
+        let time = if all_hours {
+            described_minutes
+        } else if described_minutes.starts_with("every ") {
+            format!("{} during {}", described_minutes, HOURS.describe(&hours))
+        } else {
+            format!("{} past {}", described_minutes, HOURS.describe(&hours))
+        };
+        if on_the_minute {
+            return description::capitalize(&time);
+        }
 
-impl NextPeriod {
-    fn new(after: &u8, possibilities: &[u8]) -> NextPeriod {
-        for period in possibilities {
-            if period >= after {
-                return NextPeriod {
-                    period: *period,
-                    overflow: 0,
-                };
+        let described_seconds = if SECONDS.is_full(&seconds) {
+            "every second".to_string()
+        } else {
+            at(SECONDS.describe(&seconds))
+        };
+        if all_minutes && all_hours {
+            return description::capitalize(&described_seconds);
+        }
+        let description = match (time.strip_prefix("at "), described_seconds.starts_with("every ")) {
+            (Some(time), _) => format!("{} of {}", described_seconds, time),
+            (None, false) => format!("{}, {}", time, described_seconds),
+            (None, true) if all_minutes => format!("{} during {}", described_seconds, HOURS.describe(&hours)),
+            (None, true) => format!("{} during {}", described_seconds, time),
+        };
+        description::capitalize(&description)
+    }
+
+    /// The days this runs on, or `None` for every day. When both day fields are restricted in the Vixie dialect a
+    /// day matching either of them is enough.
+    fn describe_days(&self) -> Option<String> {
+        let all_days_of_month = DAYS_OF_MONTH.is_full(&values(self.days_of_month));
+        let all_days_of_week = DAYS_OF_WEEK.is_full(&values(self.days_of_week));
+        let either = self.either_day();
+        let days_of_month = format!("on {}", self.describe_days_of_month());
+        let days_of_week = self.describe_days_of_week();
+
+        match (all_days_of_month, all_days_of_week) {
+            (true, true) => None,
+            (true, false) | (false, true) if either => None,
+            // Rules such as "the 3rd Friday of the month" read as dates, like the days of the month
+            (true, false) if self.day_rules.iter().any(|rule| rule.is_day_of_week()) => {
+                Some(format!("on {}", days_of_week))
             }
+            (true, false) => Some(days_of_week),
+            (false, true) => Some(days_of_month),
+            (false, false) if either => Some(format!("{} or on {}", days_of_month, days_of_week)),
+            (false, false) => Some(format!("{} if it's {}", days_of_month, days_of_week)),
         }
-        NextPeriod {
-            period: possibilities[0],
-            overflow: 1,
+    }
+
+    fn describe_days_of_month(&self) -> String {
+        let mut phrases = vec![];
+        if self.days_of_month != 0 {
+            phrases.push(DAYS_OF_MONTH.describe(&values(self.days_of_month)));
         }
+        phrases.extend(self.describe_day_rules(false));
+        description::list(&phrases)
+    }
+
+    /// The rules written in the day-of-week field if `day_of_week`, otherwise those in the day-of-month field.
+    fn describe_day_rules(&self, day_of_week: bool) -> Vec<String> {
+        self.day_rules
+            .iter()
+            .filter(|rule| rule.is_day_of_week() == day_of_week)
+            .map(|rule| rule.describe())
+            .collect()
+    }
+
+    /// Days of the week in whichever order needs fewer phrases, preferring weeks starting on Monday.
+    fn describe_days_of_week(&self) -> String {
+        let days_of_week = values(self.days_of_week);
+        let from_monday: Vec<u16> = days_of_week.iter().map(|&day| if day == 0 { 7 } else { day }).collect();
+        let mut phrases = vec![];
+        if !days_of_week.is_empty() {
+            phrases.push(
+                if DAYS_OF_WEEK_FROM_MONDAY.complexity(&from_monday) <= DAYS_OF_WEEK.complexity(&days_of_week) {
+                    DAYS_OF_WEEK_FROM_MONDAY.describe(&from_monday)
+                } else {
+                    DAYS_OF_WEEK.describe(&days_of_week)
+                },
+            );
+        }
+        phrases.extend(self.describe_day_rules(true));
+        description::list(&phrases)
+    }
+
+    /// An iCalendar `RRULE` value with the same local times as this, or `None` if there isn't one because the day
+    /// fields are restricted and either can match, some days depend on the month, or only some years match.
+    pub fn rrule(&self) -> Option<String> {
+        if self.either_day() || !self.day_rules.is_empty() || self.years.is_some() {
+            return None;
+        }
+        let join = |values: Vec<String>| values.join(",");
+        let numbers = |values: Vec<u16>| join(values.iter().map(u16::to_string).collect());
+
+        let mut parts = vec!["FREQ=DAILY".to_string()];
+        if self.months.count_ones() < 12 {
+            parts.push(format!("BYMONTH={}", numbers(values(self.months))));
+        }
+        if self.days_of_month.count_ones() < 31 {
+            parts.push(format!("BYMONTHDAY={}", numbers(values(self.days_of_month))));
+        }
+        if self.days_of_week.count_ones() < 7 {
+            let days = values(self.days_of_week)
+                .iter()
+                .map(|&day| RRULE_WEEKDAYS[usize::from(day)].to_string())
+                .collect();
+            parts.push(format!("BYDAY={}", join(days)));
+        }
+        parts.push(format!("BYHOUR={}", numbers(values(self.hours))));
+        parts.push(format!("BYMINUTE={}", numbers(values(self.minutes))));
+        if self.seconds != 1 {
+            parts.push(format!("BYSECOND={}", numbers(values(self.seconds))));
+        }
+        Some(parts.join(";"))
+    }
+
+    /// Whether this ever runs. Every date falls on each day of the week within the 28 year cycle of leap years and
+    /// weekdays, so only days of month which don't exist in any of the months, such as February 30th, can rule out
+    /// every date. February 29th exists, if only in leap years. Days which depend on the month are looked for
+    /// throughout the cycle.
+    pub fn is_satisfiable(&self) -> bool {
+        if let Some(ref years) = self.years {
+            return years.iter().any(|&year| {
+                (1..=12)
+                    .filter(|&month| contains(self.months, month))
+                    .any(|month| self.days_in(i32::from(year), month).is_some_and(|days| days != 0))
+            });
+        }
+        if !self.day_rules.is_empty() {
+            return (LEAP_YEAR_CYCLE_START..LEAP_YEAR_CYCLE_START + 28).any(|year| {
+                (1..=12)
+                    .filter(|&month| contains(self.months, month))
+                    .any(|month| self.days_in(year, month).is_some_and(|days| days != 0))
+            });
+        }
+        if self.either_day() {
+            return true;
+        }
+        // Only the first day of month matters, since any later one exists in fewer months
+        let first_day = self.days_of_month.trailing_zeros();
+        (1..=12)
+            .filter(|&month| contains(self.months, month))
+            .any(|month| first_day <= u32::from(DAYS_IN_MONTH[month as usize - 1]))
+    }
+
+    /// Whether this runs at the minute of `instant`, or at its second in dialects with seconds.
+    pub fn matches(&self, instant: NaiveDateTime) -> bool {
+        (!self.dialect.has_seconds() || contains(self.seconds, instant.second()))
+            && self
+                .years
+                .as_ref()
+                .is_none_or(|years| u16::try_from(instant.year()).is_ok_and(|year| years.binary_search(&year).is_ok()))
+            && contains(self.minutes, instant.minute())
+            && contains(self.hours, instant.hour())
+            && contains(self.months, instant.month())
+            && self.matches_day(instant.date())
+    }
+
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        self.days_in(date.year(), date.month())
+            .is_some_and(|days| contains(days, date.day()))
+    }
+
+    /// Whether a day matching either day field is enough, which is the case when both are restricted in the Vixie
+    /// dialect.
+    fn either_day(&self) -> bool {
+        self.dialect == Dialect::Vixie && !self.days_of_month_wildcard && !self.days_of_week_wildcard
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(&self.describe())
+    }
+}
+
+/// `phrase` as a point in time, such as "at minute 0", unless it's a repetition such as "every 15 minutes".
+fn at(phrase: String) -> String {
+    if phrase.starts_with("every ") {
+        phrase
+    } else {
+        format!("at {}", phrase)
+    }
+}
+
+/// The values whose bits are set in `bits`, in order.
+fn values<Bits: Into<u64>>(bits: Bits) -> Vec<u16> {
+    let bits = bits.into();
+    (0..64).filter(|&value| contains(bits, u32::from(value))).collect()
+}
+
+fn contains<Bits: Into<u64>>(bits: Bits, value: u32) -> bool {
+    value < 64 && bits.into() & 1 << value != 0
+}
+
+/// The smallest value in `bits` which is at least `from`, found by counting trailing zeros.
+fn next_value<Bits: Into<u64>>(bits: Bits, from: u32) -> Option<u32> {
+    match bits.into().checked_shr(from) {
+        None | Some(0) => None,
+        Some(remaining) => Some(from + remaining.trailing_zeros()),
+    }
+}
+
+/// The largest value in `bits` which is at most `to`, found by counting leading zeros.
+fn previous_value<Bits: Into<u64>>(bits: Bits, to: i32) -> Option<i32> {
+    let up_to = match to {
+        _ if to < 0 => return None,
+        _ if to >= 63 => u64::MAX,
+        _ => (2 << to) - 1,
+    };
+    match bits.into() & up_to {
+        0 => None,
+        remaining => Some(63 - remaining.leading_zeros() as i32),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{NextPeriod, Recurrence};
+    use super::{next_value, previous_value, Recurrence};
     use chrono::prelude::*;
+    use chrono::Duration;
+    use chrono_tz::America::New_York;
+    use crontab::Dialect;
 
     const ANY_SECOND: u32 = 59;
 
+    fn datetime(year: i32, month: u32, day: u32, hour: u32, minute: u32, second: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, second)
+            .unwrap()
+    }
+
     #[test]
     fn should_construct_a_recurrence_from_parser_responses() {
-        let recurrence = Recurrence::new(&vec!["1", "2", "3", "4", "5"]);
+        let recurrence = Recurrence::new(&["1", "2", "3", "4", "5"]).unwrap();
         assert_eq!(
             recurrence,
             Recurrence {
-                minutes: vec![1],
-                hours: vec![2],
-                days_of_month: vec![3],
-                months: vec![4],
-                days_of_week: vec![5],
+                seconds: 1,
+                minutes: 1 << 1,
+                hours: 1 << 2,
+                days_of_month: 1 << 3,
+                months: 1 << 4,
+                days_of_week: 1 << 5,
+                years: None,
+                day_rules: vec![],
+                days_of_month_wildcard: false,
+                days_of_week_wildcard: false,
+                time_wildcard: false,
+                dialect: Dialect::Vixie,
             }
         );
     }
 
+    #[test]
+    fn should_report_error_span_within_joined_fields() {
+        let error = Recurrence::new(&["1", "2", "3", "13", "5"]).unwrap_err();
+        assert_eq!(error.position().span, 6..8);
+    }
+
     #[test]
     fn should_get_occurrence_next_minute() {
         let recurrence = Recurrence {
-            minutes: vec![0, 1],
-            hours: vec![0],
-            days_of_month: vec![1],
-            months: vec![1],
-            days_of_week: vec![0, 1, 2, 3, 4, 5, 6],
+            seconds: 1,
+            minutes: 1 << 0 | 1 << 1,
+            hours: 1 << 0,
+            days_of_month: 1 << 1,
+            months: 1 << 1,
+            days_of_week: 0x7f,
+            years: None,
+            day_rules: vec![],
+            days_of_month_wildcard: false,
+            days_of_week_wildcard: true,
+            time_wildcard: false,
+            dialect: Dialect::Vixie,
         };
-        let now = NaiveDate::from_ymd(2000, 1, 1).and_hms(0, 0, ANY_SECOND);
-        assert_eq!(
-            recurrence.next_match(now),
-            NaiveDate::from_ymd(2000, 1, 1).and_hms(0, 1, 0)
-        );
+        let now = datetime(2000, 1, 1, 0, 0, ANY_SECOND);
+        assert_eq!(recurrence.next_match(now), Some(datetime(2000, 1, 1, 0, 1, 0)));
     }
 
     #[test]
     fn should_get_occurrence_across_hour_boundary() {
         let recurrence = Recurrence {
-            minutes: vec![0],
-            hours: vec![1],
-            days_of_month: vec![1],
-            months: vec![1],
-            days_of_week: vec![0, 1, 2, 3, 4, 5, 6],
+            seconds: 1,
+            minutes: 1 << 0,
+            hours: 1 << 1,
+            days_of_month: 1 << 1,
+            months: 1 << 1,
+            days_of_week: 0x7f,
+            years: None,
+            day_rules: vec![],
+            days_of_month_wildcard: false,
+            days_of_week_wildcard: true,
+            time_wildcard: false,
+            dialect: Dialect::Vixie,
         };
-        let now = NaiveDate::from_ymd(2000, 1, 1).and_hms(0, 59, ANY_SECOND);
-        assert_eq!(
-            recurrence.next_match(now),
-            NaiveDate::from_ymd(2000, 1, 1).and_hms(1, 0, 0)
-        );
+        let now = datetime(2000, 1, 1, 0, 59, ANY_SECOND);
+        assert_eq!(recurrence.next_match(now), Some(datetime(2000, 1, 1, 1, 0, 0)));
     }
 
     #[test]
     fn should_get_occurrence_across_day_boundary() {
         let recurrence = Recurrence {
-            minutes: vec![0],
-            hours: vec![0],
-            days_of_month: vec![2],
-            months: vec![1],
-            days_of_week: vec![0, 1, 2, 3, 4, 5, 6],
+            seconds: 1,
+            minutes: 1 << 0,
+            hours: 1 << 0,
+            days_of_month: 1 << 2,
+            months: 1 << 1,
+            days_of_week: 0x7f,
+            years: None,
+            day_rules: vec![],
+            days_of_month_wildcard: false,
+            days_of_week_wildcard: true,
+            time_wildcard: false,
+            dialect: Dialect::Vixie,
         };
-        let now = NaiveDate::from_ymd(2000, 1, 1).and_hms(0, 0, ANY_SECOND);
-        assert_eq!(
-            recurrence.next_match(now),
-            NaiveDate::from_ymd(2000, 1, 2).and_hms(0, 0, 0)
-        );
+        let now = datetime(2000, 1, 1, 0, 0, ANY_SECOND);
+        assert_eq!(recurrence.next_match(now), Some(datetime(2000, 1, 2, 0, 0, 0)));
     }
 
     #[test]
     fn should_get_occurrence_across_year_boundary() {
         let recurrence = Recurrence {
-            minutes: vec![0],
-            hours: vec![0],
-            days_of_month: vec![1],
-            months: vec![1],
-            days_of_week: vec![0],
+            seconds: 1,
+            minutes: 1 << 0,
+            hours: 1 << 0,
+            days_of_month: 1 << 1,
+            months: 1 << 1,
+            days_of_week: 0x7f,
+            years: None,
+            day_rules: vec![],
+            days_of_month_wildcard: false,
+            days_of_week_wildcard: true,
+            time_wildcard: false,
+            dialect: Dialect::Vixie,
         };
-        let now = NaiveDate::from_ymd(2000, 1, 1).and_hms(0, 0, ANY_SECOND);
+        let now = datetime(2000, 1, 1, 0, 0, ANY_SECOND);
+        assert_eq!(recurrence.next_match(now), Some(datetime(2001, 1, 1, 0, 0, 0)));
+    }
+
+    #[test]
+    fn should_start_later_hour_from_first_minute() {
+        let recurrence = Recurrence::new(&["0,30", "9,17", "*", "*", "*"]).unwrap();
+        let now = datetime(2000, 1, 1, 10, 15, 0);
+        assert_eq!(recurrence.next_match(now), Some(datetime(2000, 1, 1, 17, 0, 0)));
+    }
+
+    #[test]
+    fn should_start_later_day_from_first_time() {
+        let recurrence = Recurrence::new(&["0,30", "9,17", "*", "*", "1"]).unwrap();
+        // Saturday
+        let now = datetime(2000, 1, 1, 9, 10, 0);
+        assert_eq!(recurrence.next_match(now), Some(datetime(2000, 1, 3, 9, 0, 0)));
+    }
+
+    #[test]
+    fn should_never_match_missing_day_of_month() {
+        let now = datetime(2000, 1, 1, 0, 0, 0);
+        for fields in &[
+            ["0", "0", "30", "2", "*"],
+            ["0", "0", "31", "2", "*"],
+            ["0", "0", "31", "4", "*"],
+        ] {
+            assert_eq!(Recurrence::new(fields).unwrap().next_match(now), None);
+        }
+    }
+
+    #[test]
+    fn should_get_next_leap_day() {
+        let recurrence = Recurrence::new(&["0", "0", "29", "2", "*"]).unwrap();
+        let now = datetime(2000, 3, 1, 0, 0, 0);
+        assert_eq!(recurrence.next_match(now), Some(datetime(2004, 2, 29, 0, 0, 0)));
+    }
+
+    #[test]
+    fn should_get_leap_day_on_day_of_week() {
+        let recurrence =
+            Recurrence::new_at(&["0", "0", "29", "2", "1"], &[0, 2, 4, 7, 9], Dialect::Conjunctive, "").unwrap();
+        let now = datetime(2000, 3, 1, 0, 0, 0);
+        assert_eq!(recurrence.next_match(now), Some(datetime(2016, 2, 29, 0, 0, 0)));
+    }
+
+    #[test]
+    fn should_agree_with_checking_every_minute() {
+        let schedules = [
+            ["*/7", "*/5", "*", "*", "*"],
+            ["0,30", "9,17", "*", "*", "1"],
+            ["15", "3", "31", "*", "*"],
+            ["0", "0", "13", "*", "5"],
+            ["0", "12", "*/2", "2,3", "*"],
+            ["0", "2", "L", "*", "*"],
+            ["0", "9", "15W", "*", "5#3"],
+        ];
+        for fields in &schedules {
+            let recurrence = Recurrence::new(fields).unwrap();
+            let mut current = datetime(2000, 1, 30, 23, 50, 0);
+            for _ in 0..10 {
+                let next = recurrence.next_match(current).unwrap();
+                let mut minute = current + Duration::minutes(1);
+                while minute < next {
+                    assert!(!recurrence.matches(minute), "{:?} matches {}", fields, minute);
+                    minute += Duration::minutes(1);
+                }
+                assert!(recurrence.matches(next), "{:?} doesn't match {}", fields, next);
+                current = next;
+            }
+        }
+    }
+
+    #[test]
+    fn should_agree_with_checking_every_minute_backwards() {
+        let schedules = [
+            ["*/7", "*/5", "*", "*", "*"],
+            ["0,30", "9,17", "*", "*", "1"],
+            ["15", "3", "31", "*", "*"],
+            ["0", "0", "13", "*", "5"],
+            ["0", "12", "*/2", "2,3", "*"],
+            ["0", "2", "L", "*", "*"],
+            ["0", "9", "15W", "*", "5#3"],
+        ];
+        for fields in &schedules {
+            let recurrence = Recurrence::new(fields).unwrap();
+            let mut current = datetime(2000, 3, 2, 0, 10, 30);
+            for _ in 0..10 {
+                let previous = recurrence.previous_match(current).unwrap();
+                let mut minute = datetime(
+                    current.year(),
+                    current.month(),
+                    current.day(),
+                    current.hour(),
+                    current.minute(),
+                    0,
+                ) - Duration::minutes(1);
+                while minute > previous {
+                    assert!(!recurrence.matches(minute), "{:?} matches {}", fields, minute);
+                    minute -= Duration::minutes(1);
+                }
+                assert!(recurrence.matches(previous), "{:?} doesn't match {}", fields, previous);
+                current = previous;
+            }
+        }
+    }
+
+    #[test]
+    fn should_get_days_depending_on_month() {
+        let after = datetime(2026, 10, 18, 0, 0, 0);
+        let next = |fields: &[&str]| Recurrence::new(fields).unwrap().next_match(after);
+        assert_eq!(next(&["0", "2", "L", "*", "*"]), Some(datetime(2026, 10, 31, 2, 0, 0)));
+        assert_eq!(next(&["0", "2", "LW", "*", "*"]), Some(datetime(2026, 10, 30, 2, 0, 0)));
+        // November 15th 2026 is a Sunday
+        assert_eq!(
+            next(&["0", "9", "15W", "*", "*"]),
+            Some(datetime(2026, 11, 16, 9, 0, 0))
+        );
+        assert_eq!(
+            next(&["0", "9", "*", "*", "5#3"]),
+            Some(datetime(2026, 11, 20, 9, 0, 0))
+        );
+        assert_eq!(next(&["0", "9", "*", "*", "5L"]), Some(datetime(2026, 10, 30, 9, 0, 0)));
+        assert_eq!(next(&["0", "9", "*", "2", "1#5"]), Some(datetime(2044, 2, 29, 9, 0, 0)));
+    }
+
+    #[test]
+    fn should_get_previous_match_strictly_before_time() {
+        let recurrence = Recurrence::new(&["*", "*", "*", "*", "*"]).unwrap();
+        assert_eq!(
+            recurrence.previous_match(datetime(2000, 1, 1, 0, 0, 30)),
+            Some(datetime(2000, 1, 1, 0, 0, 0))
+        );
+        assert_eq!(
+            recurrence.previous_match(datetime(2000, 1, 1, 0, 0, 0)),
+            Some(datetime(1999, 12, 31, 23, 59, 0))
+        );
+    }
+
+    #[test]
+    fn should_get_next_match_to_the_second() {
+        let fields = ["*/20", "*", "*", "*", "*", "*"];
+        let starts = [0; 6];
+        let recurrence = Recurrence::new_at(&fields, &starts, Dialect::Spring, "").unwrap();
+        let after = datetime(2000, 1, 1, 23, 59, 45);
+        assert_eq!(recurrence.next_match(after), Some(datetime(2000, 1, 2, 0, 0, 0)));
+        assert_eq!(recurrence.previous_match(after), Some(datetime(2000, 1, 1, 23, 59, 40)));
+        assert_eq!(recurrence.describe(), "Every 20 seconds");
+    }
+
+    #[test]
+    fn should_only_match_in_listed_years() {
+        let fields = ["0", "0", "12", "?", "*", "MON-FRI", "2026"];
+        let starts = [0; 7];
+        let recurrence = Recurrence::new_at(&fields, &starts, Dialect::Quartz, "").unwrap();
+        assert_eq!(
+            recurrence.next_match(datetime(2020, 6, 1, 0, 0, 0)),
+            Some(datetime(2026, 1, 1, 12, 0, 0))
+        );
+        assert_eq!(recurrence.next_match(datetime(2026, 12, 31, 12, 0, 0)), None);
         assert_eq!(
-            recurrence.next_match(now),
-            NaiveDate::from_ymd(2001, 1, 1).and_hms(0, 0, 0)
+            recurrence.previous_match(datetime(2030, 1, 1, 0, 0, 0)),
+            Some(datetime(2026, 12, 31, 12, 0, 0))
         );
+        assert_eq!(recurrence.describe(), "At 12:00, Monday through Friday, in year 2026");
+    }
+
+    #[test]
+    fn should_describe_seconds_within_minutes() {
+        let fields = ["30", "*/15", "*", "*", "*", "*"];
+        let starts = [0; 6];
+        let recurrence = Recurrence::new_at(&fields, &starts, Dialect::Spring, "").unwrap();
+        assert_eq!(recurrence.describe(), "Every 15 minutes, at second 30");
+        let fields = ["30", "0,20", "9", "*", "*", "*"];
+        let recurrence = Recurrence::new_at(&fields, &starts, Dialect::Spring, "").unwrap();
+        assert_eq!(recurrence.describe(), "At 09:00:30 and 09:20:30");
+        let fields = ["30", "*/15", "9-17", "*", "*", "*"];
+        let recurrence = Recurrence::new_at(&fields, &starts, Dialect::Spring, "").unwrap();
+        assert_eq!(
+            recurrence.describe(),
+            "Every 15 minutes during hours 9 through 17, at second 30"
+        );
+    }
+
+    #[test]
+    fn should_get_previous_leap_day() {
+        let recurrence = Recurrence::new(&["0", "0", "29", "2", "*"]).unwrap();
+        let before = datetime(2004, 2, 28, 0, 0, 0);
+        assert_eq!(recurrence.previous_match(before), Some(datetime(2000, 2, 29, 0, 0, 0)));
+    }
+
+    #[test]
+    fn should_never_have_previous_match_of_missing_day_of_month() {
+        let recurrence = Recurrence::new(&["0", "0", "30", "2", "*"]).unwrap();
+        assert_eq!(recurrence.previous_match(datetime(2000, 1, 1, 0, 0, 0)), None);
     }
 
     #[test]
-    fn should_get_next_period_without_overflow() {
-        let next = NextPeriod::new(&20, &vec![0, 30]);
-        assert_eq!(next.period, 30);
-        assert_eq!(next.overflow, 0);
+    fn should_get_previous_run_right_after_skipped_time() {
+        let recurrence = Recurrence::new(&["15,45", "2", "*", "*", "*"]).unwrap();
+        let before = New_York.with_ymd_and_hms(2021, 3, 14, 12, 0, 0).unwrap();
+        let first = recurrence.previous_match_in(&before).unwrap();
+        assert_eq!(first, New_York.with_ymd_and_hms(2021, 3, 14, 3, 0, 0).unwrap());
+        let second = recurrence.previous_match_in(&first).unwrap();
+        assert_eq!(second, New_York.with_ymd_and_hms(2021, 3, 13, 2, 45, 0).unwrap());
     }
 
     #[test]
-    fn should_get_next_period_with_overflow() {
-        let next = NextPeriod::new(&46, &vec![15, 45]);
-        assert_eq!(next.period, 15);
-        assert_eq!(next.overflow, 1);
+    fn should_get_previous_value_from_bits() {
+        assert_eq!(previous_value(0b1010_0000u64, 7), Some(7));
+        assert_eq!(previous_value(0b1010_0000u64, 6), Some(5));
+        assert_eq!(previous_value(0b1010_0000u64, 4), None);
+        assert_eq!(previous_value(1u64 << 63, 100), Some(63));
+    }
+
+    #[test]
+    fn should_get_next_value_from_bits() {
+        assert_eq!(next_value(0b1010_0000u64, 5), Some(5));
+        assert_eq!(next_value(0b1010_0000u64, 6), Some(7));
+        assert_eq!(next_value(0b1010_0000u64, 8), None);
+        assert_eq!(next_value(u64::MAX, 64), None);
     }
 
     #[test]
     fn should_match_occurrences() {
         let recurrence = Recurrence {
-            minutes: vec![0],
-            hours: vec![0],
-            days_of_month: vec![1],
-            months: vec![1],
-            days_of_week: vec![0],
+            seconds: 1,
+            minutes: 1 << 0,
+            hours: 1 << 0,
+            days_of_month: 1 << 1,
+            months: 1 << 1,
+            days_of_week: 1 << 1,
+            years: None,
+            day_rules: vec![],
+            days_of_month_wildcard: false,
+            days_of_week_wildcard: false,
+            time_wildcard: false,
+            dialect: Dialect::Conjunctive,
         };
-        assert!(recurrence.matches(NaiveDate::from_ymd(2001, 1, 1).and_hms(0, 0, ANY_SECOND)));
+        assert!(recurrence.matches(datetime(2001, 1, 1, 0, 0, ANY_SECOND)));
     }
 
     #[test]
     fn should_not_match_other_date() {
         let recurrence = Recurrence {
-            minutes: vec![0],
-            hours: vec![0],
-            days_of_month: vec![1],
-            months: vec![1],
-            days_of_week: vec![0],
+            seconds: 1,
+            minutes: 1 << 0,
+            hours: 1 << 0,
+            days_of_month: 1 << 1,
+            months: 1 << 1,
+            days_of_week: 1 << 1,
+            years: None,
+            day_rules: vec![],
+            days_of_month_wildcard: false,
+            days_of_week_wildcard: false,
+            time_wildcard: false,
+            dialect: Dialect::Conjunctive,
         };
-        assert!(!recurrence.matches(NaiveDate::from_ymd(2000, 1, 1).and_hms(0, 0, ANY_SECOND)));
+        assert!(!recurrence.matches(datetime(2000, 1, 1, 0, 0, ANY_SECOND)));
+    }
+
+    #[test]
+    fn should_match_either_restricted_day_field() {
+        let recurrence = Recurrence::new(&["0", "0", "13", "*", "5"]).unwrap();
+        // Friday the 13th, another 13th, another Friday
+        assert!(recurrence.matches(datetime(2000, 10, 13, 0, 0, 0)));
+        assert!(recurrence.matches(datetime(2000, 11, 13, 0, 0, 0)));
+        assert!(recurrence.matches(datetime(2000, 11, 17, 0, 0, 0)));
+        assert!(!recurrence.matches(datetime(2000, 11, 16, 0, 0, 0)));
+    }
+
+    #[test]
+    fn should_get_every_13th_and_every_friday() {
+        let recurrence = Recurrence::new(&["0", "0", "13", "*", "5"]).unwrap();
+        let mut current = datetime(2000, 11, 1, 0, 0, 0);
+        let mut actual = vec![];
+        for _ in 0..4 {
+            current = recurrence.next_match(current).unwrap();
+            actual.push(current.day());
+        }
+        assert_eq!(actual, vec![3, 10, 13, 17]);
+    }
+
+    #[test]
+    fn should_not_be_satisfiable_on_missing_day_of_month() {
+        assert!(!Recurrence::new(&["0", "0", "30", "2", "*"]).unwrap().is_satisfiable());
+        assert!(!Recurrence::new(&["0", "0", "31", "4,6,9,11", "*"])
+            .unwrap()
+            .is_satisfiable());
+    }
+
+    #[test]
+    fn should_be_satisfiable_on_leap_day_or_either_day_field() {
+        assert!(Recurrence::new(&["0", "0", "29", "2", "*"]).unwrap().is_satisfiable());
+        assert!(Recurrence::new(&["0", "0", "30", "2", "1"]).unwrap().is_satisfiable());
+    }
+
+    #[test]
+    fn should_reject_no_specific_value_without_seconds() {
+        let error = Recurrence::new(&["0", "0", "?", "*", "*"]).unwrap_err();
+        assert_eq!(error.position().span, 4..5);
+        assert!(Recurrence::new_at(&["0", "0", "0", "?", "*", "*"], &[0; 6], Dialect::Spring, "").is_ok());
+    }
+
+    #[test]
+    fn should_require_day_of_week_when_day_of_month_is_wildcard() {
+        let recurrence = Recurrence::new(&["0", "0", "*/2", "*", "5"]).unwrap();
+        assert!(recurrence.matches(datetime(2000, 11, 3, 0, 0, 0)));
+        assert!(!recurrence.matches(datetime(2000, 11, 5, 0, 0, 0)));
+        assert!(!recurrence.matches(datetime(2000, 11, 10, 0, 0, 0)));
+    }
+
+    #[test]
+    fn should_require_day_of_month_when_day_of_week_is_wildcard() {
+        let recurrence = Recurrence::new(&["0", "0", "13", "*", "*"]).unwrap();
+        assert!(recurrence.matches(datetime(2000, 11, 13, 0, 0, 0)));
+        assert!(!recurrence.matches(datetime(2000, 11, 17, 0, 0, 0)));
+    }
+
+    #[test]
+    fn should_require_both_day_fields_in_conjunctive_dialect() {
+        let recurrence =
+            Recurrence::new_at(&["0", "0", "13", "*", "5"], &[0, 2, 4, 7, 9], Dialect::Conjunctive, "").unwrap();
+        assert!(recurrence.matches(datetime(2000, 10, 13, 0, 0, 0)));
+        assert!(!recurrence.matches(datetime(2000, 11, 13, 0, 0, 0)));
+        assert!(!recurrence.matches(datetime(2000, 11, 17, 0, 0, 0)));
+    }
+
+    #[test]
+    fn should_count_sunday_as_day_zero() {
+        let recurrence = Recurrence::new(&["0", "0", "*", "*", "0"]).unwrap();
+        assert!(recurrence.matches(datetime(2000, 1, 2, 0, 0, 0)));
+        assert!(!recurrence.matches(datetime(2000, 1, 3, 0, 0, 0)));
+    }
+
+    #[test]
+    fn should_express_daily_times_as_rrule() {
+        let recurrence = Recurrence::new(&["30", "6,18", "*", "*", "*"]).unwrap();
+        assert_eq!(
+            recurrence.rrule(),
+            Some("FREQ=DAILY;BYHOUR=6,18;BYMINUTE=30".to_string())
+        );
+    }
+
+    #[test]
+    fn should_express_restricted_dates_as_rrule() {
+        let recurrence = Recurrence::new(&["0", "9", "*/10", "jan-mar", "5-7"]).unwrap();
+        assert_eq!(
+            recurrence.rrule(),
+            Some("FREQ=DAILY;BYMONTH=1,2,3;BYMONTHDAY=1,11,21,31;BYDAY=SU,FR,SA;BYHOUR=9;BYMINUTE=0".to_string())
+        );
+    }
+
+    fn describe(fields: &[&str]) -> String {
+        Recurrence::new(fields).unwrap().describe()
+    }
+
+    #[test]
+    fn should_describe_every_minute() {
+        assert_eq!(describe(&["*", "*", "*", "*", "*"]), "Every minute");
+    }
+
+    #[test]
+    fn should_describe_times_of_day() {
+        assert_eq!(describe(&["30", "6,18", "*", "*", "*"]), "At 06:30 and 18:30");
+    }
+
+    #[test]
+    fn should_describe_steps_ranges_and_names() {
+        assert_eq!(
+            describe(&["0", "9-17/2", "*", "jan,jul", "mon-fri"]),
+            "At minute 0 past every 2nd hour from 9 through 17, Monday through Friday, in January and July"
+        );
+    }
+
+    #[test]
+    fn should_describe_steps_over_whole_field_as_lengths_of_time() {
+        for minutes in &["*/5", "0-59/5"] {
+            assert_eq!(describe(&[minutes, "*", "*", "*", "*"]), "Every 5 minutes");
+        }
+        assert_eq!(describe(&["*/7", "*", "*", "*", "*"]), "Every 7th minute");
+        assert_eq!(describe(&["*/25", "*", "*", "*", "*"]), "Every 25th minute");
+        assert_eq!(
+            describe(&["0", "9-17", "*", "*", "*"]),
+            "At minute 0 past hours 9 through 17"
+        );
+    }
+
+    #[test]
+    fn should_collapse_overlapping_list_items() {
+        assert_eq!(
+            describe(&["5-9/2,1,*/5", "*", "*", "*", "*"]),
+            "Every 5 minutes and minutes 1, 7 and 9"
+        );
+    }
+
+    #[test]
+    fn should_describe_every_minute_of_some_hours() {
+        assert_eq!(
+            describe(&["*", "9-17", "*", "*", "*"]),
+            "Every minute during hours 9 through 17"
+        );
+    }
+
+    #[test]
+    fn should_describe_days_of_month() {
+        assert_eq!(
+            describe(&["0", "0", "1,15", "*", "*"]),
+            "At 00:00, on days-of-month 1 and 15"
+        );
+    }
+
+    #[test]
+    fn should_describe_weekend_in_order() {
+        assert_eq!(describe(&["0", "10", "*", "*", "6,0"]), "At 10:00, Saturday and Sunday");
+    }
+
+    #[test]
+    fn should_describe_either_restricted_day_field() {
+        assert_eq!(
+            describe(&["0", "0", "13", "*", "5"]),
+            "At 00:00, on day-of-month 13 or on Friday"
+        );
+    }
+
+    #[test]
+    fn should_describe_both_day_fields_when_one_starts_with_wildcard() {
+        assert_eq!(
+            describe(&["0", "0", "*/2", "*", "1"]),
+            "At 00:00, on every 2nd day-of-month if it's Monday"
+        );
+    }
+
+    #[test]
+    fn should_describe_days_depending_on_month() {
+        assert_eq!(
+            describe(&["0", "2", "1,L", "*", "*"]),
+            "At 02:00, on day-of-month 1 and the last day of the month"
+        );
+        assert_eq!(
+            describe(&["0", "9", "*", "*", "5#3"]),
+            "At 09:00, on the 3rd Friday of the month"
+        );
+        assert_eq!(
+            describe(&["0", "9", "*", "*", "1,5L"]),
+            "At 09:00, on Monday and the last Friday of the month"
+        );
+        assert_eq!(
+            describe(&["0", "9", "15W", "*", "*"]),
+            "At 09:00, on the weekday nearest day-of-month 15"
+        );
+    }
+
+    #[test]
+    fn should_describe_both_day_fields_in_conjunctive_dialect() {
+        let recurrence = Recurrence::new_at(&["0", "0", "13", "*", "5"], &[0; 5], Dialect::Conjunctive, "").unwrap();
+        assert_eq!(recurrence.to_string(), "At 00:00, on day-of-month 13 if it's Friday");
+    }
+
+    #[test]
+    fn should_not_express_either_day_field_as_rrule() {
+        let recurrence = Recurrence::new(&["0", "0", "13", "*", "5"]).unwrap();
+        assert_eq!(recurrence.rrule(), None);
+    }
+
+    #[test]
+    fn should_express_both_day_fields_as_rrule_in_conjunctive_dialect() {
+        let recurrence = Recurrence::new_at(&["0", "0", "13", "*", "5"], &[0; 5], Dialect::Conjunctive, "").unwrap();
+        assert_eq!(
+            recurrence.rrule(),
+            Some("FREQ=DAILY;BYMONTHDAY=13;BYDAY=FR;BYHOUR=0;BYMINUTE=0".to_string())
+        );
+    }
+
+    #[test]
+    fn should_get_occurrence_in_fixed_offset_zone() {
+        let recurrence = Recurrence::new(&["30", "2", "*", "*", "*"]).unwrap();
+        let zone = FixedOffset::east_opt(12 * 3600).unwrap();
+        let after = zone.with_ymd_and_hms(2000, 1, 1, 3, 0, 0).unwrap();
+        let actual = recurrence.next_match_in(&after).unwrap();
+        assert_eq!(actual, zone.with_ymd_and_hms(2000, 1, 2, 2, 30, 0).unwrap());
+        assert_eq!(actual.naive_utc(), datetime(2000, 1, 1, 14, 30, 0));
+    }
+
+    #[test]
+    fn should_run_once_right_after_skipped_time() {
+        let recurrence = Recurrence::new(&["15,45", "2", "*", "*", "*"]).unwrap();
+        let after = New_York.with_ymd_and_hms(2021, 3, 14, 0, 0, 0).unwrap();
+        let first = recurrence.next_match_in(&after).unwrap();
+        assert_eq!(first, New_York.with_ymd_and_hms(2021, 3, 14, 3, 0, 0).unwrap());
+        let second = recurrence.next_match_in(&first).unwrap();
+        assert_eq!(second, New_York.with_ymd_and_hms(2021, 3, 15, 2, 15, 0).unwrap());
+    }
+
+    #[test]
+    fn should_run_repeated_time_once() {
+        let recurrence = Recurrence::new(&["30", "1", "*", "*", "*"]).unwrap();
+        let after = New_York.with_ymd_and_hms(2021, 11, 7, 0, 0, 0).unwrap();
+        let first = recurrence.next_match_in(&after).unwrap();
+        assert_eq!(first.naive_utc(), datetime(2021, 11, 7, 5, 30, 0));
+        let second = recurrence.next_match_in(&first).unwrap();
+        assert_eq!(second, New_York.with_ymd_and_hms(2021, 11, 8, 1, 30, 0).unwrap());
+    }
+
+    #[test]
+    fn should_skip_skipped_time_with_wildcard_hour() {
+        let recurrence = Recurrence::new(&["30", "*", "*", "*", "*"]).unwrap();
+        let after = New_York.with_ymd_and_hms(2021, 3, 14, 1, 0, 0).unwrap();
+        let first = recurrence.next_match_in(&after).unwrap();
+        assert_eq!(first, New_York.with_ymd_and_hms(2021, 3, 14, 1, 30, 0).unwrap());
+        let second = recurrence.next_match_in(&first).unwrap();
+        assert_eq!(second, New_York.with_ymd_and_hms(2021, 3, 14, 3, 30, 0).unwrap());
+        assert_eq!(recurrence.previous_match_in(&second), Some(first));
+    }
+
+    #[test]
+    fn should_repeat_frequent_runs_in_repeated_hour() {
+        let recurrence = Recurrence::new(&["*/30", "*", "*", "*", "*"]).unwrap();
+        let after = New_York.with_ymd_and_hms(2021, 11, 7, 0, 45, 0).unwrap();
+        let mut runs = vec![after];
+        for _ in 0..5 {
+            let next = recurrence.next_match_in(runs.last().unwrap()).unwrap();
+            runs.push(next);
+        }
+        let expected: Vec<NaiveDateTime> = [(5, 0), (5, 30), (6, 0), (6, 30), (7, 0)]
+            .iter()
+            .map(|&(hour, minute)| datetime(2021, 11, 7, hour, minute, 0))
+            .collect();
+        assert_eq!(runs[1..].iter().map(DateTime::naive_utc).collect::<Vec<_>>(), expected);
+        for pair in runs[1..].windows(2) {
+            assert_eq!(recurrence.previous_match_in(&pair[1]), Some(pair[0]));
+        }
+    }
+
+    fn to_expression(fields: &[&str]) -> String {
+        Recurrence::new(fields).unwrap().to_expression()
+    }
+
+    #[test]
+    fn should_write_equivalent_schedules_the_same_way() {
+        for minutes in &["0,15,30,45", "*/15", "0-59/15", "0-45/15"] {
+            assert_eq!(to_expression(&[minutes, "*", "*", "*", "*"]), "*/15 * * * *");
+        }
+        assert_eq!(
+            to_expression(&["5", "9-12,13-17", "*", "jan-mar", "*"]),
+            "5 9-17 * 1-3 *"
+        );
+        assert_eq!(to_expression(&["0", "0", "*", "*", "sun,fri,sat"]), "0 0 * * 5-7");
+    }
+
+    #[test]
+    fn should_keep_day_fields_restricted_or_unrestricted() {
+        assert_eq!(to_expression(&["0", "0", "1-31", "*", "*"]), "0 0 1-31 * *");
+        assert_eq!(to_expression(&["0", "0", "*/2", "*", "mon"]), "0 0 */2 * 1");
+        assert_eq!(to_expression(&["0", "0", "*", "*", "0-7"]), "0 0 * * 0-6");
+        assert_eq!(to_expression(&["0", "0", "L,1", "*", "5#3"]), "0 0 1,L * 5#3");
+    }
+
+    #[test]
+    fn should_keep_minute_and_hour_fixed_or_wildcard() {
+        assert_eq!(to_expression(&["0-59/15", "0-23", "*", "*", "*"]), "0-45/15 0-23 * * *");
+        assert_eq!(to_expression(&["0", "*/1", "*", "*", "*"]), "0 * * * *");
+        assert_eq!(to_expression(&["0-45/15", "*/12", "*", "*", "*"]), "*/15 0,12 * * *");
+        assert_eq!(to_expression(&["0,20", "*/12", "*", "*", "*"]), "0,20 */12 * * *");
+    }
+
+    #[test]
+    fn should_parse_expression_back_to_same_recurrence() {
+        let schedules = [
+            (Dialect::Vixie, "*/7 9-17,20 L,15W * 5L"),
+            (Dialect::Vixie, "1,2,3,5,8,13 */5 */3 jan-mar,dec sat,sun"),
+            (Dialect::Vixie, "0-59/15 0-23 * * *"),
+            (Dialect::Vixie, "0,30 */1 * * *"),
+            (Dialect::Conjunctive, "0 0 */3 * 1-5"),
+            (Dialect::Spring, "*/10 0 12 ? * MON-FRI"),
+            (Dialect::Spring, "0 0 12 * * *"),
+            (Dialect::Quartz, "0 0 12 ? * 6L,1 2026-2030/2,2040"),
+        ];
+        for &(dialect, schedule) in &schedules {
+            let parse = |schedule: &str| {
+                let fields: Vec<&str> = schedule.split(' ').collect();
+                Recurrence::new_at(&fields, &vec![0; fields.len()], dialect, "").unwrap()
+            };
+            let recurrence = parse(schedule);
+            assert_eq!(parse(&recurrence.to_expression()), recurrence, "{}", schedule);
+        }
+    }
+
+    #[test]
+    fn should_keep_star_on_minute_or_hour_through_expression() {
+        let minutes = ["14", "*/7,3", "*/20,1-5", "5-59/10", "*", "*/59"];
+        let hours = ["*/12,1", "3", "*/5,7", "*", "1-23/2", "*/23,4"];
+        for minute in &minutes {
+            for hour in &hours {
+                let recurrence = Recurrence::new(&[minute, hour, "*", "*", "*"]).unwrap();
+                let expression = recurrence.to_expression();
+                let fields: Vec<&str> = expression.split(' ').collect();
+                assert_eq!(Recurrence::new(&fields).unwrap(), recurrence, "{} {}", minute, hour);
+            }
+        }
+        assert_eq!(to_expression(&["14", "*/12,1", "*", "*", "*"]), "14 */12,1 * * *");
+    }
+
+    #[test]
+    fn should_write_no_specific_value_in_dialects_with_seconds() {
+        let fields = ["0", "0", "12", "*", "*", "*"];
+        let recurrence = Recurrence::new_at(&fields, &[0; 6], Dialect::Spring, "").unwrap();
+        assert_eq!(recurrence.to_expression(), "0 0 12 * * ?");
+        let fields = ["0", "0", "12", "?", "*", "2", "*"];
+        let recurrence = Recurrence::new_at(&fields, &[0; 7], Dialect::Quartz, "").unwrap();
+        assert_eq!(recurrence.to_expression(), "0 0 12 ? * 2 *");
     }
 }
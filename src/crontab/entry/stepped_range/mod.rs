@@ -24,9 +24,12 @@ where
     T: Debug,
     T: Integer,
 {
-    pub fn new(start: T, end: T, step: T) -> SteppedRange<T> {
-        assert_ne!(step, T::zero());
-        SteppedRange { start, end, step }
+    /// Returns `None` if `step` is zero, since such a range would never end.
+    pub fn new(start: T, end: T, step: T) -> Option<SteppedRange<T>> {
+        if step.is_zero() {
+            return None;
+        }
+        Some(SteppedRange { start, end, step })
     }
 }
 
@@ -56,20 +59,19 @@ mod tests {
 
     #[test]
     fn should_return_none_for_empty_range() {
-        let mut range = SteppedRange::new(0, 0, 1);
+        let mut range = SteppedRange::new(0, 0, 1).unwrap();
         assert_eq!(range.next(), None);
     }
 
     #[test]
     fn should_return_one_item_for_trivial_range() {
-        let mut range = SteppedRange::new(0, 1, 1);
+        let mut range = SteppedRange::new(0, 1, 1).unwrap();
         assert_eq!(range.next(), Some(0));
         assert_eq!(range.next(), None);
     }
 
     #[test]
-    #[should_panic]
     fn should_fail_with_step_of_zero() {
-        SteppedRange::new(0, 0, 0);
+        assert!(SteppedRange::new(0, 0, 0).is_none());
     }
 }
@@ -0,0 +1,38 @@
+use super::recurrence::Recurrence;
+use chrono::{DateTime, TimeZone};
+use std::fmt;
+
+/// What makes cron run an entry.
+#[derive(Debug, PartialEq)]
+pub enum Trigger {
+    Recurrence(Recurrence),
+    /// `@reboot`, which runs once when cron starts rather than at any particular time.
+    Reboot,
+}
+
+impl Trigger {
+    /// The next time after `after` this triggers, if it's timed at all and ever runs.
+    pub fn next_match<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        match *self {
+            Trigger::Recurrence(ref recurrence) => recurrence.next_match_in(after),
+            Trigger::Reboot => None,
+        }
+    }
+
+    /// The last time before `before` this triggered, if it's timed at all and ever runs.
+    pub fn previous_match<Tz: TimeZone>(&self, before: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        match *self {
+            Trigger::Recurrence(ref recurrence) => recurrence.previous_match_in(before),
+            Trigger::Reboot => None,
+        }
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Trigger::Recurrence(ref recurrence) => recurrence.fmt(formatter),
+            Trigger::Reboot => formatter.write_str("At boot"),
+        }
+    }
+}
@@ -0,0 +1,130 @@
+/// Variables assigned in a crontab, in the order they were first assigned.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Environment<'a> {
+    variables: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Environment<'a> {
+    /// The value of the last assignment to `name`.
+    pub fn get(&self, name: &str) -> Option<&'a str> {
+        self.variables
+            .iter()
+            .find(|&&(variable, _)| variable == name)
+            .map(|&(_, value)| value)
+    }
+
+    /// Assigns `value` to `name`, keeping the position of any earlier assignment.
+    pub fn set(&mut self, name: &'a str, value: &'a str) {
+        match self.variables.iter_mut().find(|&&mut (variable, _)| variable == name) {
+            Some(variable) => variable.1 = value,
+            None => self.variables.push((name, value)),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, &'a str)> + '_ {
+        self.variables.iter().cloned()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.variables.is_empty()
+    }
+
+    /// Parses a `NAME=value` line the way cron does: either side may be quoted, whitespace around `=` is ignored,
+    /// and unquoted values lose their trailing whitespace.
+    pub fn parse_assignment(line: &'a str) -> Option<(&'a str, &'a str)> {
+        let line = line.trim();
+        let (name, rest) = match Environment::split_quoted(line) {
+            Some(split) => split,
+            None => {
+                let end = line
+                    .find(|character: char| character == '=' || character.is_whitespace())
+                    .unwrap_or(line.len());
+                (&line[..end], &line[end..])
+            }
+        };
+        let rest = rest.trim_start();
+        if name.is_empty() || !rest.starts_with('=') {
+            return None;
+        }
+
+        let value = rest[1..].trim_start();
+        match Environment::split_quoted(value) {
+            Some((quoted, "")) => Some((name, quoted)),
+            _ => Some((name, value)),
+        }
+    }
+
+    /// Splits a leading single- or double-quoted string from the rest of `text`.
+    fn split_quoted(text: &str) -> Option<(&str, &str)> {
+        let quote = text
+            .chars()
+            .next()
+            .filter(|&character| character == '"' || character == '\'')?;
+        let end = text[1..].find(quote)? + 1;
+        Some((&text[1..end], text[end + 1..].trim_end()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Environment;
+
+    #[test]
+    fn should_parse_assignment() {
+        assert_eq!(
+            Environment::parse_assignment("MAILTO=ops@example.com"),
+            Some(("MAILTO", "ops@example.com"))
+        );
+    }
+
+    #[test]
+    fn should_parse_assignment_with_spaces_around_equals_sign() {
+        assert_eq!(
+            Environment::parse_assignment("  PATH = /usr/bin  "),
+            Some(("PATH", "/usr/bin"))
+        );
+    }
+
+    #[test]
+    fn should_parse_double_quoted_value() {
+        assert_eq!(
+            Environment::parse_assignment("GREETING=\"hello  world \""),
+            Some(("GREETING", "hello  world "))
+        );
+    }
+
+    #[test]
+    fn should_parse_single_quoted_name_and_value() {
+        assert_eq!(Environment::parse_assignment("'A B' = 'c d'"), Some(("A B", "c d")));
+    }
+
+    #[test]
+    fn should_keep_quotes_which_do_not_enclose_the_whole_value() {
+        assert_eq!(Environment::parse_assignment("A=\"b\" c"), Some(("A", "\"b\" c")));
+    }
+
+    #[test]
+    fn should_parse_empty_value() {
+        assert_eq!(Environment::parse_assignment("MAILTO=\"\""), Some(("MAILTO", "")));
+        assert_eq!(Environment::parse_assignment("MAILTO="), Some(("MAILTO", "")));
+    }
+
+    #[test]
+    fn should_not_parse_entry_as_assignment() {
+        assert_eq!(Environment::parse_assignment("*/5 * * * * FOO=bar command"), None);
+        assert_eq!(Environment::parse_assignment("@daily A=b"), None);
+    }
+
+    #[test]
+    fn should_override_earlier_assignment_in_place() {
+        let mut environment = Environment::default();
+        environment.set("SHELL", "/bin/sh");
+        environment.set("PATH", "/bin");
+        environment.set("SHELL", "/bin/bash");
+        assert_eq!(environment.get("SHELL"), Some("/bin/bash"));
+        assert_eq!(
+            environment.iter().collect::<Vec<_>>(),
+            vec![("SHELL", "/bin/bash"), ("PATH", "/bin")]
+        );
+    }
+}
@@ -1,52 +1,197 @@
-use chrono::NaiveDateTime;
-use crontab::entry::Entry;
+pub use self::crontab_kind::CrontabKind;
+pub use self::dialect::Dialect;
+pub use self::document::{Anchor, Document, Line, LineKind};
+pub use self::edit_error::EditError;
+pub use self::entry::{DateTimeFieldParser, Entry, Recurrence, Trigger};
+pub use self::environment::Environment;
+pub use self::parse_error::{ParseError, Position};
+pub use self::parse_options::ParseOptions;
+pub use self::runs::{Runs, RunsBefore};
 
+use chrono::{DateTime, TimeZone};
+use chrono_tz::Tz;
+use std::path::Path;
+
+mod crontab_kind;
+mod dialect;
+mod document;
+mod edit_error;
 mod entry;
+mod environment;
+mod parse_error;
+mod parse_options;
+mod runs;
+
+/// Variables which set the zone entries are scheduled in, in order of precedence.
+const TIMEZONE_VARIABLES: [&str; 2] = ["CRON_TZ", "TZ"];
 
+#[derive(Default)]
 pub struct Crontab<'a> {
     entries: Vec<Entry<'a>>,
+    /// Every variable assigned in the crontab, with the last value assigned to each.
+    pub environment: Environment<'a>,
 }
 
 impl<'a> Crontab<'a> {
-    pub fn new(input: &str) -> Crontab {
-        Crontab {
-            entries: input
-                .lines()
-                .map(|line| line.trim_left())
-                .filter(|line| !line.starts_with('#'))
-                .filter(|line| !line.is_empty())
-                .map(|line| Entry::new(line))
-                .collect(),
+    /// Parses every entry, failing on the first line which can't be parsed.
+    pub fn new(input: &'a str) -> Result<Crontab<'a>, ParseError> {
+        let (crontab, mut errors) = Crontab::new_lenient(input);
+        if errors.is_empty() {
+            Ok(crontab)
+        } else {
+            Err(errors.remove(0))
+        }
+    }
+
+    /// Parses every entry, keeping the valid ones and returning an error for each of the others.
+    pub fn new_lenient(input: &'a str) -> (Crontab<'a>, Vec<ParseError>) {
+        Crontab::parse(input, &ParseOptions::default())
+    }
+
+    /// Like `new_lenient`, interpreting lines according to `options`.
+    pub fn parse(input: &'a str, options: &ParseOptions) -> (Crontab<'a>, Vec<ParseError>) {
+        let lines = input.lines().map(|line| (line, LineKind::of(line)));
+        let (results, environment) = Crontab::parse_lines(lines, options);
+        let mut entries = vec![];
+        let mut errors = vec![];
+        for result in results {
+            match result {
+                Ok(entry) => entries.push(entry),
+                Err(error) => errors.push(error),
+            }
         }
+        (Crontab { entries, environment }, errors)
     }
 
-    pub fn next_run(&self, from: NaiveDateTime) -> Run {
-        self.entries
+    /// Parses the assignments and entries among `lines`, giving each entry the environment and zone assigned before
+    /// it. Returns every entry and error in line order, and the environment after the last line.
+    fn parse_lines<'b, Lines>(
+        lines: Lines,
+        options: &ParseOptions,
+    ) -> (Vec<Result<Entry<'b>, ParseError>>, Environment<'b>)
+    where
+        Lines: Iterator<Item = (&'b str, LineKind)>,
+    {
+        // Look up the current user only once
+        let options = &ParseOptions {
+            user: Some(options.owner()),
+            ..options.clone()
+        };
+        let mut results = vec![];
+        let mut environment = Environment::default();
+        let mut timezone = None;
+
+        for (index, (line, kind)) in lines.enumerate() {
+            match kind {
+                LineKind::Blank | LineKind::Comment => continue,
+                LineKind::Assignment | LineKind::Entry => {}
+            }
+            if let Some((name, value)) = Environment::parse_assignment(line) {
+                environment.set(name, value);
+                if TIMEZONE_VARIABLES.contains(&name) {
+                    timezone = Crontab::timezone(&environment).unwrap_or_else(|name| {
+                        let start = value.as_ptr() as usize - line.as_ptr() as usize;
+                        results.push(Err(ParseError::UnknownTimeZone {
+                            name,
+                            position: Position {
+                                line: index + 1,
+                                span: start..start + value.len(),
+                            },
+                        }));
+                        None
+                    });
+                }
+                continue;
+            }
+            results.push(
+                Entry::parse(line, options)
+                    .map(|mut entry| {
+                        entry.environment = environment.clone();
+                        entry.timezone = timezone;
+                        entry.line = index + 1;
+                        entry
+                    })
+                    .map_err(|error| error.on_line(index + 1)),
+            );
+        }
+
+        (results, environment)
+    }
+
+    /// The zone named by the first of `TIMEZONE_VARIABLES` which is set, or the name if it's not a known zone.
+    fn timezone(environment: &Environment) -> Result<Option<Tz>, String> {
+        match TIMEZONE_VARIABLES
             .iter()
-            .map(|entry| Run {
-                entry,
-                datetime: entry.recurrence.next_match(from),
-            })
-            .min_by(|this, other| this.datetime.cmp(&other.datetime))
-            .unwrap()
+            .filter_map(|name| environment.get(name))
+            .next()
+        {
+            None | Some("") => Ok(None),
+            Some(name) => name
+                .trim_start_matches(':')
+                .parse()
+                .map(Some)
+                .map_err(|_| name.to_string()),
+        }
+    }
+
+    /// Records `path` as the file every entry was read from.
+    pub fn set_source(&mut self, path: &'a Path) {
+        for entry in &mut self.entries {
+            entry.source = Some(path);
+        }
+    }
+
+    /// Adds the entries of `other` after the existing ones.
+    pub fn append(&mut self, mut other: Crontab<'a>) {
+        self.entries.append(&mut other.entries);
+    }
+
+    /// Every entry, in file order.
+    pub fn entries(&self) -> &[Entry<'a>] {
+        &self.entries
+    }
+
+    /// Entries which run when cron starts, in file order.
+    pub fn reboot_entries(&self) -> impl Iterator<Item = &Entry<'a>> {
+        self.entries.iter().filter(|entry| entry.trigger == Trigger::Reboot)
+    }
+
+    pub fn next_run<Zone: TimeZone>(&self, from: &DateTime<Zone>) -> Option<Run<'_, Zone>> {
+        self.runs_from(from).next()
+    }
+
+    /// Every upcoming run after `from`, in chronological order and in the zone of `from`.
+    pub fn runs_from<Zone: TimeZone>(&self, from: &DateTime<Zone>) -> Runs<'_, Zone> {
+        Runs::new(&self.entries, from)
+    }
+
+    /// Every past run before `to`, in reverse chronological order and in the zone of `to`.
+    pub fn runs_before<Zone: TimeZone>(&self, to: &DateTime<Zone>) -> RunsBefore<'_, Zone> {
+        RunsBefore::new(&self.entries, to)
     }
 }
 
-pub struct Run<'a> {
+#[derive(Clone)]
+pub struct Run<'a, Zone: TimeZone> {
     pub entry: &'a Entry<'a>,
-    pub datetime: NaiveDateTime,
+    pub datetime: DateTime<Zone>,
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Crontab;
-    use chrono::NaiveDate;
-    use chrono::NaiveDateTime;
-    use crontab::entry::Entry;
+    use super::{Crontab, CrontabKind, Dialect, ParseError, ParseOptions, Position};
+    use chrono::prelude::*;
+    use chrono_tz::Pacific::Auckland;
+    use std::path::Path;
+    use test_support::user_options;
+
+    fn datetime(year: i32, month: u32, day: u32, hour: u32, minute: u32, second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, second).unwrap()
+    }
 
     #[test]
     fn should_create_single_entry() {
-        let actual = Crontab::new("1 2 3 4 5 command");
+        let actual = Crontab::new("1 2 3 4 5 command").unwrap();
         assert_eq!(actual.entries.len(), 1);
         assert_eq!(actual.entries[0].command, "command");
     }
@@ -54,7 +199,7 @@ mod tests {
     #[test]
     fn should_create_multiple_entries() {
         let crontab = ["1 2 3 4 5 first", "2 3 4 5 6 second"].join("\n");
-        let actual = Crontab::new(&crontab);
+        let actual = Crontab::new(&crontab).unwrap();
         assert_eq!(actual.entries.len(), 2);
         assert_eq!(actual.entries[0].command, "first");
         assert_eq!(actual.entries[1].command, "second");
@@ -63,7 +208,7 @@ mod tests {
     #[test]
     fn should_ignore_empty_lines() {
         let crontab = ["", " \t \t", "1 2 3 4 5 command", " ", ""].join("\n");
-        let actual = Crontab::new(&crontab);
+        let actual = Crontab::new(&crontab).unwrap();
         assert_eq!(actual.entries.len(), 1);
         assert_eq!(actual.entries[0].command, "command");
     }
@@ -71,17 +216,330 @@ mod tests {
     #[test]
     fn should_ignore_comment_lines() {
         let crontab = ["# Comment", "1 2 3 4 5 command", " \t \t# Comment"].join("\n");
-        let actual = Crontab::new(&crontab);
+        let actual = Crontab::new(&crontab).unwrap();
         assert_eq!(actual.entries.len(), 1);
         assert_eq!(actual.entries[0].command, "command");
     }
 
+    #[test]
+    fn should_parse_environment_lines() {
+        let crontab = ["MAILTO=ops@example.com", "PATH = /usr/bin:/bin", "1 2 3 4 5 command"].join("\n");
+        let actual = Crontab::new(&crontab).unwrap();
+        assert_eq!(actual.entries.len(), 1);
+        assert_eq!(actual.environment.get("MAILTO"), Some("ops@example.com"));
+        assert_eq!(actual.environment.get("PATH"), Some("/usr/bin:/bin"));
+    }
+
+    #[test]
+    fn should_give_entries_the_environment_at_their_line() {
+        let crontab = [
+            "1 2 3 4 5 first",
+            "MAILTO=ops@example.com",
+            "1 2 3 4 5 second",
+            "MAILTO=\"\"",
+            "SHELL=/bin/bash",
+            "1 2 3 4 5 third",
+        ]
+        .join("\n");
+        let actual = Crontab::new(&crontab).unwrap();
+        assert!(actual.entries[0].environment.is_empty());
+        assert_eq!(actual.entries[1].environment.get("MAILTO"), Some("ops@example.com"));
+        assert_eq!(actual.entries[1].environment.get("SHELL"), None);
+        assert_eq!(actual.entries[2].environment.get("MAILTO"), Some(""));
+        assert_eq!(actual.entries[2].environment.get("SHELL"), Some("/bin/bash"));
+    }
+
+    #[test]
+    fn should_run_user_crontab_entries_as_owner() {
+        let options = user_options();
+        let (crontab, errors) = Crontab::parse("1 2 3 4 5 root command", &options);
+        assert!(errors.is_empty());
+        assert_eq!(crontab.entries[0].user, "alice");
+        assert_eq!(crontab.entries[0].command, "root command");
+    }
+
+    #[test]
+    fn should_run_user_crontab_entries_as_current_user_by_default() {
+        let crontab = Crontab::new("1 2 3 4 5 command").unwrap();
+        assert_eq!(crontab.entries[0].user, ParseOptions::default().owner());
+    }
+
+    #[test]
+    fn should_parse_system_crontab_user_column() {
+        let options = ParseOptions {
+            kind: CrontabKind::System,
+            ..ParseOptions::default()
+        };
+        let crontab = [
+            "SHELL=/bin/sh",
+            "17 * * * * root    cd / && run-parts --report /etc/cron.hourly",
+            "@reboot www-data /usr/local/bin/warm-cache",
+        ]
+        .join("\n");
+        let (crontab, errors) = Crontab::parse(&crontab, &options);
+        assert!(errors.is_empty());
+        assert_eq!(crontab.entries[0].user, "root");
+        assert_eq!(
+            crontab.entries[0].command,
+            "cd / && run-parts --report /etc/cron.hourly"
+        );
+        assert_eq!(crontab.entries[1].user, "www-data");
+        assert_eq!(crontab.entries[1].command, "/usr/local/bin/warm-cache");
+    }
+
+    #[test]
+    fn should_report_missing_command_after_user() {
+        let options = ParseOptions {
+            kind: CrontabKind::System,
+            ..ParseOptions::default()
+        };
+        let (_, errors) = Crontab::parse("1 2 3 4 5 root", &options);
+        assert_eq!(
+            errors,
+            vec![ParseError::MissingCommand {
+                position: Position { line: 1, span: 14..14 },
+            }]
+        );
+    }
+
     #[test]
     fn should_get_next_run() {
         let crontab = ["0 * * * * first", "30 * * * * second"].join("\n");
-        let crontab = Crontab::new(&crontab);
-        let next_run = crontab.next_run(NaiveDate::from_ymd(2000, 1, 1).and_hms(0, 10, 0));
+        let crontab = Crontab::new(&crontab).unwrap();
+        let next_run = crontab.next_run(&datetime(2000, 1, 1, 0, 10, 0)).unwrap();
         assert_eq!(next_run.entry.command, "second");
-        assert_eq!(next_run.datetime, NaiveDate::from_ymd(2000, 1, 1).and_hms(0, 30, 0));
+        assert_eq!(next_run.datetime, datetime(2000, 1, 1, 0, 30, 0));
+    }
+
+    #[test]
+    fn should_merge_runs_in_chronological_order() {
+        let crontab = ["0 * * * * hourly", "*/20 * * * * third", "10 0 * * * daily"].join("\n");
+        let crontab = Crontab::new(&crontab).unwrap();
+        let actual: Vec<(DateTime<Utc>, &str)> = crontab
+            .runs_from(&datetime(2000, 1, 1, 0, 0, 0))
+            .take(7)
+            .map(|run| (run.datetime, run.entry.command.as_ref()))
+            .collect();
+        assert_eq!(
+            actual,
+            vec![
+                (datetime(2000, 1, 1, 0, 10, 0), "daily"),
+                (datetime(2000, 1, 1, 0, 20, 0), "third"),
+                (datetime(2000, 1, 1, 0, 40, 0), "third"),
+                (datetime(2000, 1, 1, 1, 0, 0), "hourly"),
+                (datetime(2000, 1, 1, 1, 0, 0), "third"),
+                (datetime(2000, 1, 1, 1, 20, 0), "third"),
+                (datetime(2000, 1, 1, 1, 40, 0), "third"),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_break_ties_in_file_order() {
+        let crontab = ["*/30 * * * * first", "30 * * * * second", "*/30 * * * * third"].join("\n");
+        let crontab = Crontab::new(&crontab).unwrap();
+        let actual: Vec<&str> = crontab
+            .runs_from(&datetime(2000, 1, 1, 0, 10, 0))
+            .take(3)
+            .map(|run| run.entry.command.as_ref())
+            .collect();
+        assert_eq!(actual, vec!["first", "second", "third"]);
+    }
+
+    #[test]
+    fn should_list_runs_until_end() {
+        let crontab = Crontab::new("*/15 * * * * command").unwrap();
+        let to = datetime(2000, 1, 1, 1, 0, 0);
+        let actual = crontab
+            .runs_from(&datetime(2000, 1, 1, 0, 0, 0))
+            .take_while(|run| run.datetime <= to)
+            .count();
+        assert_eq!(actual, 4);
+    }
+
+    #[test]
+    fn should_list_runs_before_in_reverse() {
+        let crontab = Crontab::new("0 * * * * first\n30 * * * * second\n0 * * * * third").unwrap();
+        let to = datetime(2000, 1, 1, 1, 0, 0);
+        let forward: Vec<_> = crontab
+            .runs_from(&datetime(2000, 1, 1, 0, 0, 0))
+            .take_while(|run| run.datetime < to)
+            .map(|run| (run.datetime, run.entry.command.as_ref()))
+            .collect();
+        let mut backward: Vec<_> = crontab
+            .runs_before(&to)
+            .take(forward.len())
+            .map(|run| (run.datetime, run.entry.command.as_ref()))
+            .collect();
+        backward.reverse();
+        assert_eq!(backward, forward);
+    }
+
+    #[test]
+    fn should_skip_reboot_entries_when_listing_runs() {
+        let crontab = ["@reboot first", "0 * * * * second"].join("\n");
+        let crontab = Crontab::new(&crontab).unwrap();
+        let actual: Vec<&str> = crontab
+            .runs_from(&datetime(2000, 1, 1, 0, 0, 0))
+            .take(2)
+            .map(|run| run.entry.command.as_ref())
+            .collect();
+        assert_eq!(actual, vec!["second", "second"]);
+    }
+
+    #[test]
+    fn should_skip_entries_which_never_run() {
+        let crontab = Crontab::new("0 0 30 2 * never\n0 0 * * * daily\n").unwrap();
+        let runs: Vec<_> = crontab
+            .runs_from(&datetime(2000, 1, 1, 0, 0, 0))
+            .take(2)
+            .map(|run| run.entry.command.as_ref())
+            .collect();
+        assert_eq!(runs, vec!["daily", "daily"]);
+    }
+
+    #[test]
+    fn should_list_reboot_entries() {
+        let crontab = ["@reboot first", "0 * * * * second", "@reboot third"].join("\n");
+        let crontab = Crontab::new(&crontab).unwrap();
+        let actual: Vec<&str> = crontab.reboot_entries().map(|entry| entry.command.as_ref()).collect();
+        assert_eq!(actual, vec!["first", "third"]);
+    }
+
+    #[test]
+    fn should_not_get_next_run_with_only_reboot_entries() {
+        let crontab = Crontab::new("@reboot command").unwrap();
+        assert!(crontab.next_run(&datetime(2000, 1, 1, 0, 0, 0)).is_none());
+    }
+
+    #[test]
+    fn should_not_list_runs_without_entries() {
+        let crontab = Crontab::new("").unwrap();
+        assert!(crontab.runs_from(&datetime(2000, 1, 1, 0, 0, 0)).next().is_none());
+    }
+
+    #[test]
+    fn should_append_entries() {
+        let mut crontab = Crontab::new("1 2 3 4 5 first").unwrap();
+        crontab.append(Crontab::new("2 3 4 5 6 second").unwrap());
+        assert_eq!(crontab.entries.len(), 2);
+        assert_eq!(crontab.entries[1].command, "second");
+    }
+
+    #[test]
+    fn should_record_line_of_each_entry() {
+        let crontab = ["# Comment", "1 2 3 4 5 first", "", "2 3 4 5 6 second"].join("\n");
+        let actual = Crontab::new(&crontab).unwrap();
+        assert_eq!(actual.entries[0].line, 2);
+        assert_eq!(actual.entries[1].line, 4);
+    }
+
+    #[test]
+    fn should_set_source_of_every_entry() {
+        let path = Path::new("/etc/crontab");
+        let mut crontab = Crontab::new("1 2 3 4 5 first\n2 3 4 5 6 second").unwrap();
+        crontab.set_source(path);
+        assert!(crontab.entries.iter().all(|entry| entry.source == Some(path)));
+    }
+
+    #[test]
+    fn should_not_get_next_run_without_entries() {
+        let crontab = Crontab::new("# Nothing to see here").unwrap();
+        assert!(crontab.next_run(&datetime(2000, 1, 1, 0, 0, 0)).is_none());
+    }
+
+    #[test]
+    fn should_fail_on_first_invalid_line() {
+        let crontab = ["1 2 3 4 5 first", "# Comment", "1 2 3 4 60 second", "@never third"].join("\n");
+        assert_eq!(
+            Crontab::new(&crontab).err(),
+            Some(ParseError::OutOfRange {
+                value: 60,
+                min: 0,
+                max: 7,
+                position: Position { line: 3, span: 8..10 },
+            })
+        );
+    }
+
+    #[test]
+    fn should_collect_every_error_leniently() {
+        let crontab = [
+            "1 2 3 4 5 first",
+            "1 2 3 4 60 second",
+            "@never third",
+            "5 4 3 2 1 fourth",
+        ]
+        .join("\n");
+        let (actual, errors) = Crontab::new_lenient(&crontab);
+        assert_eq!(actual.entries.len(), 2);
+        assert_eq!(actual.entries[0].command, "first");
+        assert_eq!(actual.entries[1].command, "fourth");
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].position().line, 2);
+        assert_eq!(errors[1].position().line, 3);
+    }
+
+    #[test]
+    fn should_schedule_entries_in_cron_tz() {
+        let crontab = ["0 12 * * * local", "CRON_TZ=Pacific/Auckland", "0 12 * * * auckland"].join("\n");
+        let crontab = Crontab::new(&crontab).unwrap();
+        let actual: Vec<(DateTime<Utc>, &str)> = crontab
+            .runs_from(&datetime(2000, 1, 1, 0, 0, 0))
+            .take(2)
+            .map(|run| (run.datetime, run.entry.command.as_ref()))
+            .collect();
+        assert_eq!(
+            actual,
+            vec![
+                (datetime(2000, 1, 1, 12, 0, 0), "local"),
+                (datetime(2000, 1, 1, 23, 0, 0), "auckland"),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_return_runs_in_zone_of_start() {
+        let crontab = Crontab::new("0 12 * * * command").unwrap();
+        let from = Auckland.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+        let next_run = crontab.next_run(&from).unwrap();
+        assert_eq!(
+            next_run.datetime,
+            Auckland.with_ymd_and_hms(2000, 1, 1, 12, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn should_prefer_cron_tz_to_tz() {
+        let crontab = ["TZ=UTC", "CRON_TZ=Pacific/Auckland", "0 12 * * * command"].join("\n");
+        let crontab = Crontab::new(&crontab).unwrap();
+        assert_eq!(crontab.entries[0].timezone, Some(Auckland));
+    }
+
+    #[test]
+    fn should_report_unknown_time_zone() {
+        let crontab = ["1 2 3 4 5 first", "CRON_TZ = Mars/Olympus_Mons", "1 2 3 4 5 second"].join("\n");
+        let (crontab, errors) = Crontab::new_lenient(&crontab);
+        assert_eq!(crontab.entries.len(), 2);
+        assert_eq!(crontab.entries[1].timezone, None);
+        assert_eq!(
+            errors,
+            vec![ParseError::UnknownTimeZone {
+                name: "Mars/Olympus_Mons".to_string(),
+                position: Position { line: 2, span: 10..27 },
+            }]
+        );
+    }
+
+    #[test]
+    fn should_parse_with_dialect() {
+        let options = ParseOptions {
+            dialect: Dialect::Conjunctive,
+            ..ParseOptions::default()
+        };
+        let (crontab, errors) = Crontab::parse("0 0 13 * 5 command", &options);
+        assert!(errors.is_empty());
+        let next_run = crontab.next_run(&datetime(2000, 1, 1, 0, 0, 0)).unwrap();
+        assert_eq!(next_run.datetime, datetime(2000, 10, 13, 0, 0, 0));
     }
 }
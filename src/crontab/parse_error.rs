@@ -0,0 +1,166 @@
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+/// Where in the input a parse error was found.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Position {
+    /// One-based line number, or zero when a single entry was parsed on its own.
+    pub line: usize,
+    /// Byte offsets of the offending text within the line.
+    pub span: Range<usize>,
+}
+
+impl Position {
+    pub fn new(span: Range<usize>) -> Position {
+        Position { line: 0, span }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseError {
+    UnknownNickname {
+        nickname: String,
+        position: Position,
+    },
+    BadNumber {
+        value: String,
+        position: Position,
+    },
+    OutOfRange {
+        value: u32,
+        min: u16,
+        max: u16,
+        position: Position,
+    },
+    ReversedRange {
+        first: u16,
+        last: u16,
+        position: Position,
+    },
+    ZeroStep {
+        position: Position,
+    },
+    MissingCommand {
+        position: Position,
+    },
+    TooFewFields {
+        found: usize,
+        expected: usize,
+        position: Position,
+    },
+    UnknownTimeZone {
+        name: String,
+        position: Position,
+    },
+}
+
+impl ParseError {
+    pub fn position(&self) -> &Position {
+        match *self {
+            ParseError::UnknownNickname { ref position, .. }
+            | ParseError::BadNumber { ref position, .. }
+            | ParseError::OutOfRange { ref position, .. }
+            | ParseError::ReversedRange { ref position, .. }
+            | ParseError::ZeroStep { ref position }
+            | ParseError::MissingCommand { ref position }
+            | ParseError::TooFewFields { ref position, .. }
+            | ParseError::UnknownTimeZone { ref position, .. } => position,
+        }
+    }
+
+    fn position_mut(&mut self) -> &mut Position {
+        match *self {
+            ParseError::UnknownNickname { ref mut position, .. }
+            | ParseError::BadNumber { ref mut position, .. }
+            | ParseError::OutOfRange { ref mut position, .. }
+            | ParseError::ReversedRange { ref mut position, .. }
+            | ParseError::ZeroStep { ref mut position }
+            | ParseError::MissingCommand { ref mut position }
+            | ParseError::TooFewFields { ref mut position, .. }
+            | ParseError::UnknownTimeZone { ref mut position, .. } => position,
+        }
+    }
+
+    /// Moves the span right by `offset` bytes, for errors found in a substring of the line.
+    pub fn shifted(mut self, offset: usize) -> ParseError {
+        {
+            let span = &mut self.position_mut().span;
+            *span = span.start + offset..span.end + offset;
+        }
+        self
+    }
+
+    pub fn on_line(mut self, line: usize) -> ParseError {
+        self.position_mut().line = line;
+        self
+    }
+
+    /// What's wrong, without the position.
+    pub fn message(&self) -> String {
+        match *self {
+            ParseError::UnknownNickname { ref nickname, .. } => format!("unknown datetime nickname ‘{}’", nickname),
+            ParseError::BadNumber { ref value, .. } => format!("invalid number ‘{}’", value),
+            ParseError::OutOfRange { value, min, max, .. } => {
+                format!("value {} is outside the range {}-{}", value, min, max)
+            }
+            ParseError::ReversedRange { first, last, .. } => format!("range {}-{} ends before it starts", first, last),
+            ParseError::ZeroStep { .. } => "step must be greater than zero".to_string(),
+            ParseError::MissingCommand { .. } => "missing command".to_string(),
+            ParseError::TooFewFields { found, expected, .. } => {
+                format!("expected {} time fields, found {}", expected, found)
+            }
+            ParseError::UnknownTimeZone { ref name, .. } => format!("unknown time zone ‘{}’", name),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let position = self.position();
+        write!(
+            formatter,
+            "line {}, column {}: {}",
+            position.line,
+            position.span.start + 1,
+            self.message()
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{ParseError, Position};
+
+    #[test]
+    fn should_shift_span() {
+        let error = ParseError::ZeroStep {
+            position: Position::new(2..3),
+        };
+        assert_eq!(error.shifted(10).position().span, 12..13);
+    }
+
+    #[test]
+    fn should_set_line() {
+        let error = ParseError::ZeroStep {
+            position: Position::new(2..3),
+        };
+        assert_eq!(error.on_line(7).position().line, 7);
+    }
+
+    #[test]
+    fn should_display_line_and_column() {
+        let error = ParseError::OutOfRange {
+            value: 60,
+            min: 0,
+            max: 59,
+            position: Position { line: 3, span: 4..6 },
+        };
+        assert_eq!(
+            error.to_string(),
+            "line 3, column 5: value 60 is outside the range 0-59"
+        );
+    }
+}
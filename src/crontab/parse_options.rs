@@ -0,0 +1,24 @@
+use crontab::{CrontabKind, Dialect};
+use whoami;
+
+/// Settings which affect how crontab lines are interpreted.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParseOptions {
+    pub dialect: Dialect,
+    pub kind: CrontabKind,
+    /// The owner of a user crontab, if not the current user.
+    pub user: Option<String>,
+    /// Hashed along with the command to pick the values of `H`, so that hosts sharing a crontab run it at different
+    /// times.
+    pub host_id: Option<String>,
+}
+
+impl ParseOptions {
+    /// The user entries of a user crontab run as.
+    pub fn owner(&self) -> String {
+        match self.user {
+            Some(ref user) => user.clone(),
+            None => whoami::username(),
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use chrono::{DateTime, TimeZone};
+use crontab::entry::Entry;
+use crontab::Run;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Upcoming runs of every timed entry in chronological order, with entries firing at the same time in file order.
+///
+/// Each entry only has its next run queued, so this is a k-way merge of the entries' own run sequences.
+pub struct Runs<'a, Tz: TimeZone> {
+    entries: &'a [Entry<'a>],
+    queue: BinaryHeap<Reverse<(DateTime<Tz>, usize)>>,
+}
+
+impl<'a, Tz: TimeZone> Runs<'a, Tz> {
+    pub fn new(entries: &'a [Entry<'a>], from: &DateTime<Tz>) -> Runs<'a, Tz> {
+        Runs {
+            entries,
+            queue: entries
+                .iter()
+                .enumerate()
+                .filter_map(|(index, entry)| Some(Reverse((entry.next_match(from)?, index))))
+                .collect(),
+        }
+    }
+}
+
+impl<'a, Tz: TimeZone> Iterator for Runs<'a, Tz> {
+    type Item = Run<'a, Tz>;
+
+    fn next(&mut self) -> Option<Run<'a, Tz>> {
+        let Reverse((datetime, index)) = self.queue.pop()?;
+        let entry = &self.entries[index];
+        if let Some(next) = entry.next_match(&datetime) {
+            self.queue.push(Reverse((next, index)));
+        }
+        Some(Run { entry, datetime })
+    }
+}
+
+/// Past runs of every timed entry in reverse chronological order, exactly the reverse of `Runs`.
+pub struct RunsBefore<'a, Tz: TimeZone> {
+    entries: &'a [Entry<'a>],
+    queue: BinaryHeap<(DateTime<Tz>, usize)>,
+}
+
+impl<'a, Tz: TimeZone> RunsBefore<'a, Tz> {
+    pub fn new(entries: &'a [Entry<'a>], to: &DateTime<Tz>) -> RunsBefore<'a, Tz> {
+        RunsBefore {
+            entries,
+            queue: entries
+                .iter()
+                .enumerate()
+                .filter_map(|(index, entry)| Some((entry.previous_match(to)?, index)))
+                .collect(),
+        }
+    }
+}
+
+impl<'a, Tz: TimeZone> Iterator for RunsBefore<'a, Tz> {
+    type Item = Run<'a, Tz>;
+
+    fn next(&mut self) -> Option<Run<'a, Tz>> {
+        let (datetime, index) = self.queue.pop()?;
+        let entry = &self.entries[index];
+        if let Some(previous) = entry.previous_match(&datetime) {
+            self.queue.push((previous, index));
+        }
+        Some(Run { entry, datetime })
+    }
+}
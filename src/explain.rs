@@ -0,0 +1,178 @@
+//! Crontabs annotated with what each entry does.
+
+use chrono::{DateTime, TimeZone};
+use crontab::{Crontab, ParseOptions, Trigger};
+use lint::{lint, Check};
+use std::fmt::Display;
+
+/// Upcoming runs listed above each entry.
+const RUN_COUNT: usize = 3;
+const DATETIME_FORMAT: &str = "%Y-%m-%d %k:%M";
+const DATETIME_WITH_SECONDS_FORMAT: &str = "%Y-%m-%d %k:%M:%S";
+
+/// `input` with a comment above each entry describing its schedule and next runs after `from`, and one above each
+/// line for each problem `lint` finds in it. Every line of `input` is kept exactly as it was.
+pub fn explain<Zone: TimeZone>(input: &str, options: &ParseOptions, from: &DateTime<Zone>) -> String
+where
+    Zone::Offset: Display,
+{
+    let (crontab, _) = Crontab::parse(input, options);
+    // Entries which never run already say so
+    let diagnostics: Vec<_> = lint(input, options)
+        .into_iter()
+        .filter(|diagnostic| diagnostic.check != Check::NeverRuns)
+        .collect();
+    let mut explained = String::with_capacity(input.len() * 2);
+    let format = if options.dialect.has_seconds() {
+        DATETIME_WITH_SECONDS_FORMAT
+    } else {
+        DATETIME_FORMAT
+    };
+
+    for (index, line) in input.split_inclusive('\n').enumerate() {
+        let mut comments = vec![];
+        if let Some(entry) = crontab.entries().iter().find(|entry| entry.line == index + 1) {
+            if let Some(ref hashed_schedule) = entry.hashed_schedule {
+                comments.push(format!("Hashed schedule: {}", hashed_schedule));
+            }
+            comments.push(entry.trigger.to_string());
+            let mut runs = vec![];
+            let mut after = from.clone();
+            while runs.len() < RUN_COUNT {
+                match entry.next_match(&after) {
+                    Some(run) => {
+                        runs.push(run.format(format).to_string());
+                        after = run;
+                    }
+                    None => break,
+                }
+            }
+            match entry.trigger {
+                Trigger::Recurrence(_) if runs.is_empty() => comments.push("Never runs".to_string()),
+                _ if runs.is_empty() => {}
+                _ => comments.push(format!("Next runs: {}", runs.join(", "))),
+            }
+        }
+        for diagnostic in diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.position.line == index + 1)
+        {
+            comments.push(format!(
+                "Warning: column {}: {}",
+                diagnostic.position.span.start + 1,
+                diagnostic.message
+            ));
+        }
+
+        let indentation = &line[..line.len() - line.trim_start().len()];
+        let ending = if line.ends_with("\r\n") { "\r\n" } else { "\n" };
+        for comment in comments {
+            explained.push_str(indentation);
+            explained.push_str("# ");
+            explained.push_str(&comment);
+            explained.push_str(ending);
+        }
+        explained.push_str(line);
+    }
+
+    explained
+}
+
+#[cfg(test)]
+mod tests {
+    use super::explain;
+    use chrono::{TimeZone, Utc};
+    use crontab::{CrontabKind, ParseOptions};
+    use test_support::user_options;
+
+    fn explain_user(input: &str) -> String {
+        let options = user_options();
+        explain(input, &options, &Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap())
+    }
+
+    #[test]
+    fn should_describe_entry_and_next_runs() {
+        assert_eq!(
+            explain_user("30 6 * * 1-5 backup\n"),
+            "# At 06:30, Monday through Friday\n\
+             # Next runs: 2020-01-01  6:30, 2020-01-02  6:30, 2020-01-03  6:30\n\
+             30 6 * * 1-5 backup\n"
+        );
+    }
+
+    #[test]
+    fn should_say_when_entry_never_runs() {
+        assert_eq!(
+            explain_user("0 0 30 2 * never\n"),
+            "# At 00:00, on day-of-month 30, in February\n# Never runs\n0 0 30 2 * never\n"
+        );
+    }
+
+    #[test]
+    fn should_keep_other_lines_exactly() {
+        let input = "# Backups\r\n\r\nMAILTO = \"ops\"  \n   \t\n";
+        assert_eq!(explain_user(input), input);
+    }
+
+    #[test]
+    fn should_match_indentation_and_line_ending() {
+        assert_eq!(
+            explain_user("\t@reboot start\r\n"),
+            "\t# At boot\r\n\t@reboot start\r\n"
+        );
+    }
+
+    #[test]
+    fn should_keep_last_line_without_newline() {
+        assert_eq!(
+            explain_user("@reboot start"),
+            "# At boot\n# Warning: column 14: missing newline at the end of the file, so cron may ignore this line\n\
+             @reboot start"
+        );
+    }
+
+    #[test]
+    fn should_warn_about_invalid_lines() {
+        assert_eq!(
+            explain_user("# Comment\n60 * * * * late\n"),
+            "# Comment\n# Warning: column 1: value 60 is outside the range 0-59\n60 * * * * late\n"
+        );
+    }
+
+    #[test]
+    fn should_warn_about_likely_mistakes() {
+        let actual = explain_user("*/7 * * * * a\n0 0 13 * 5 b\n0 0 * * * c 50%\n");
+        let warnings: Vec<_> = actual.lines().filter(|line| line.starts_with("# Warning")).collect();
+        assert_eq!(
+            warnings,
+            vec![
+                "# Warning: column 1: step 7 doesn't divide the 60 minutes evenly, so minute 56 is followed by minute 0",
+                "# Warning: column 5: both day-of-month and day-of-week are restricted, so this runs on days matching \
+                 either",
+                "# Warning: column 15: unescaped ‘%’ ends the command, and the rest is sent to its standard input",
+            ]
+        );
+    }
+
+    #[test]
+    fn should_warn_about_unknown_time_zone() {
+        assert_eq!(
+            explain_user("CRON_TZ=Mars/Olympus\n"),
+            "# Warning: column 9: unknown time zone ‘Mars/Olympus’\nCRON_TZ=Mars/Olympus\n"
+        );
+    }
+
+    #[test]
+    fn should_explain_system_crontab() {
+        let options = ParseOptions {
+            kind: CrontabKind::System,
+            ..ParseOptions::default()
+        };
+        let actual = explain(
+            "0 0 * * * root\n",
+            &options,
+            &Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap(),
+        );
+        assert_eq!(actual, "# Warning: column 15: missing command\n0 0 * * * root\n");
+    }
+}
@@ -0,0 +1,168 @@
+//! Crontabs with their schedules rewritten in a canonical form.
+
+use crontab::{CrontabKind, Document, Entry, LineKind, ParseError, ParseOptions, Trigger};
+
+/// The time fields of an entry, or its nickname.
+enum Schedule<'a> {
+    Fields(Vec<String>),
+    Nickname(&'a str),
+}
+
+/// `input` with the time fields of each entry in their shortest form and the columns of the entries aligned. Nicknames
+/// and schedules using `H` are kept as written, and every other line is kept exactly as it was.
+pub fn format(input: &str, options: &ParseOptions) -> Result<String, ParseError> {
+    let document = Document::parse(input);
+    let mut rows = vec![];
+    for (index, line) in document.lines().iter().enumerate() {
+        if line.kind() != LineKind::Entry {
+            rows.push(None);
+            continue;
+        }
+        let entry = Entry::parse(&line.text, options).map_err(|error| error.on_line(index + 1))?;
+        let schedule = match entry.trigger {
+            Trigger::Recurrence(ref recurrence) if !entry.schedule.starts_with('@') => {
+                let fields = match entry.hashed_schedule {
+                    Some(_) => entry.schedule.split_whitespace().map(str::to_string).collect(),
+                    None => recurrence.to_expression().split(' ').map(str::to_string).collect(),
+                };
+                Schedule::Fields(fields)
+            }
+            _ => Schedule::Nickname(entry.schedule),
+        };
+        let user = match options.kind {
+            CrontabKind::System => Some(entry.user.to_string()),
+            CrontabKind::User => None,
+        };
+        rows.push(Some((schedule, user, entry.raw_command)));
+    }
+
+    let mut widths = vec![0; options.dialect.field_count()];
+    let mut nickname_width = 0;
+    let mut user_width = 0;
+    for (schedule, user, _) in rows.iter().flatten() {
+        match schedule {
+            Schedule::Fields(fields) => {
+                for (width, field) in widths.iter_mut().zip(fields) {
+                    *width = (*width).max(field.len());
+                }
+            }
+            Schedule::Nickname(nickname) => nickname_width = nickname_width.max(nickname.len()),
+        }
+        user_width = user_width.max(user.as_ref().map_or(0, |user| user.chars().count()));
+    }
+    // Nicknames fill the width of the time fields, which grow if a nickname is wider
+    let fields_width = widths.iter().sum::<usize>() + widths.len() - 1;
+    if let Some(last) = widths.last_mut() {
+        *last += nickname_width.saturating_sub(fields_width);
+    }
+    let schedule_width = fields_width.max(nickname_width);
+
+    let mut formatted = String::with_capacity(input.len());
+    for (line, row) in document.lines().iter().zip(rows) {
+        match row {
+            Some((schedule, user, command)) => {
+                let mut columns = match schedule {
+                    Schedule::Fields(fields) => fields
+                        .iter()
+                        .zip(&widths)
+                        .map(|(field, &width)| format!("{:width$}", field, width = width))
+                        .collect(),
+                    Schedule::Nickname(nickname) => vec![format!("{:width$}", nickname, width = schedule_width)],
+                };
+                if let Some(user) = user {
+                    columns.push(format!("{:width$}", user, width = user_width));
+                }
+                columns.push(command.to_string());
+                formatted.push_str(&columns.join(" "));
+            }
+            None => formatted.push_str(&line.text),
+        }
+        formatted.push_str(line.ending);
+    }
+    Ok(formatted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format;
+    use crontab::{CrontabKind, Dialect, ParseError, ParseOptions, Position};
+    use test_support::user_options;
+
+    fn format_user(input: &str) -> Result<String, ParseError> {
+        let options = user_options();
+        format(input, &options)
+    }
+
+    #[test]
+    fn should_write_shortest_schedules_in_aligned_columns() {
+        assert_eq!(
+            format_user("0,15,30,45 * * * * check\n0 9-12,13-17 * * mon-fri  report --daily\n").unwrap(),
+            "*/15 *    * * *   check\n\
+             0    9-17 * * 1-5 report --daily\n"
+        );
+    }
+
+    #[test]
+    fn should_keep_day_fields_restricted() {
+        assert_eq!(format_user("0 0 1-31 * */1 both\n").unwrap(), "0 0 1-31 * * both\n");
+    }
+
+    #[test]
+    fn should_keep_nicknames_and_hashes_as_written() {
+        assert_eq!(
+            format_user("@annually   yearly\nH H(0-5) * * * hashed\r\n0  0 * * * daily").unwrap(),
+            "@annually      yearly\n\
+             H H(0-5) * * * hashed\r\n\
+             0 0      * * * daily"
+        );
+    }
+
+    #[test]
+    fn should_rewrite_day_names_which_contain_h() {
+        assert_eq!(format_user("0 9 * * THU weekly\n").unwrap(), "0 9 * * 4 weekly\n");
+    }
+
+    #[test]
+    fn should_keep_other_lines_exactly() {
+        let input = "# Backups\r\n\r\nMAILTO = \"ops\"  \n   \t\n";
+        assert_eq!(format_user(input).unwrap(), input);
+    }
+
+    #[test]
+    fn should_align_users_of_system_crontab() {
+        let options = ParseOptions {
+            kind: CrontabKind::System,
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            format("0-59/20 * * * * root first\n0 0 * * * nobody second\n", &options).unwrap(),
+            "*/20 * * * * root   first\n\
+             0    0 * * * nobody second\n"
+        );
+    }
+
+    #[test]
+    fn should_write_quartz_schedules() {
+        let options = ParseOptions {
+            dialect: Dialect::Quartz,
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            format("0 0,20,40 12 * * 2-6 * weekdays\n", &options).unwrap(),
+            "0 0,20,40 12 ? * 2-6 * weekdays\n"
+        );
+    }
+
+    #[test]
+    fn should_fail_on_invalid_line() {
+        assert_eq!(
+            format_user("# Comment\n60 * * * * late\n"),
+            Err(ParseError::OutOfRange {
+                value: 60,
+                min: 0,
+                max: 59,
+                position: Position { line: 2, span: 0..2 },
+            })
+        );
+    }
+}
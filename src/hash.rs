@@ -0,0 +1,14 @@
+//! Hashing which gives the same values in every build, unlike the standard library's hashers.
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// 64-bit FNV-1a of `bytes`.
+pub fn fnv1a<Bytes>(bytes: Bytes) -> u64
+where
+    Bytes: IntoIterator<Item = u8>,
+{
+    bytes.into_iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
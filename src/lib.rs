@@ -1,5 +1,21 @@
 extern crate chrono;
+extern crate chrono_tz;
 extern crate core;
-extern crate time;
+extern crate csv;
+extern crate iana_time_zone;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate whoami;
 
 pub mod crontab;
+pub mod explain;
+pub mod format;
+mod hash;
+pub mod lint;
+pub mod output;
+pub mod run_parts;
+pub mod sources;
+#[cfg(test)]
+mod test_support;
@@ -0,0 +1,485 @@
+//! Problems in crontabs which cron accepts without complaint but probably doesn't do what was meant.
+
+use crontab::{Crontab, DateTimeFieldParser, Dialect, ParseError, ParseOptions, Position, Recurrence, Trigger};
+use std::cmp;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// Singular and plural names of the time fields, in order.
+const FIELD_NAMES: [(&str, &str); 5] = [
+    ("minute", "minutes"),
+    ("hour", "hours"),
+    ("day-of-month", "days-of-month"),
+    ("month", "months"),
+    ("day-of-week", "days-of-week"),
+];
+const SECOND_NAMES: (&str, &str) = ("second", "seconds");
+const YEAR_NAMES: (&str, &str) = ("year", "years");
+/// Indexes of the day and month fields, counting from the minutes field.
+const DAY_OF_MONTH_FIELD: usize = 2;
+const MONTH_FIELD: usize = 3;
+const DAY_OF_WEEK_FIELD: usize = 4;
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Severity {
+    Note,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(match *self {
+            Severity::Note => "note",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Severity, String> {
+        match name {
+            "note" => Ok(Severity::Note),
+            "warning" => Ok(Severity::Warning),
+            "error" => Ok(Severity::Error),
+            _ => Err(format!("unknown severity ‘{}’", name)),
+        }
+    }
+}
+
+/// What a diagnostic is about. Codes never change meaning, so they can be looked up or filtered on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Check {
+    /// A line cron can't parse.
+    InvalidEntry,
+    /// A schedule without any date, such as the 30th of February.
+    NeverRuns,
+    /// A step through the whole field which doesn't divide it, so the gap where it starts over is different.
+    UnevenStep,
+    /// Both day fields restricted, which matches days matching *either* of them.
+    EitherDay,
+    /// A list item whose values are all in other items.
+    RedundantValue,
+    /// A range which ends before it starts.
+    ReversedRange,
+    /// A `%` in a command, which cron turns into a newline and sends the rest of to standard input.
+    UnescapedPercent,
+    /// A last line without a newline, which some crons ignore.
+    MissingNewline,
+}
+
+impl Check {
+    pub fn code(self) -> &'static str {
+        match self {
+            Check::InvalidEntry => "C001",
+            Check::NeverRuns => "C002",
+            Check::UnevenStep => "C003",
+            Check::EitherDay => "C004",
+            Check::RedundantValue => "C005",
+            Check::ReversedRange => "C006",
+            Check::UnescapedPercent => "C007",
+            Check::MissingNewline => "C008",
+        }
+    }
+
+    pub fn severity(self) -> Severity {
+        match self {
+            Check::InvalidEntry | Check::NeverRuns | Check::ReversedRange => Severity::Error,
+            Check::UnevenStep | Check::EitherDay | Check::UnescapedPercent | Check::MissingNewline => Severity::Warning,
+            Check::RedundantValue => Severity::Note,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub check: Check,
+    pub message: String,
+    pub position: Position,
+}
+
+impl Diagnostic {
+    fn new(check: Check, message: String, line: usize, span: Range<usize>) -> Diagnostic {
+        Diagnostic {
+            check,
+            message,
+            position: Position { line, span },
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.check.severity()
+    }
+
+    /// The diagnostic the way rustc shows them, quoting the line of `input` with the span underlined. `name` is
+    /// where `input` was read from.
+    pub fn render(&self, name: &str, input: &str) -> String {
+        let line = input.lines().nth(self.position.line.saturating_sub(1)).unwrap_or("");
+        let start = cmp::min(self.position.span.start, line.len());
+        let end = cmp::max(start, cmp::min(self.position.span.end, line.len()));
+        let number = self.position.line.to_string();
+        let gutter = " ".repeat(number.len());
+        // Keep tabs so that the carets line up however wide they are shown
+        let indentation: String = line[..start]
+            .chars()
+            .map(|character| if character == '\t' { '\t' } else { ' ' })
+            .collect();
+        let carets = "^".repeat(cmp::max(1, line[start..end].chars().count()));
+
+        format!(
+            "{severity}[{code}]: {message}\n\
+             {gutter}--> {name}:{line_number}:{column}\n\
+             {gutter} |\n\
+             {number} | {line}\n\
+             {gutter} | {indentation}{carets}\n",
+            severity = self.severity(),
+            code = self.check.code(),
+            message = self.message,
+            gutter = gutter,
+            name = name,
+            line_number = self.position.line,
+            column = line[..start].chars().count() + 1,
+            number = number,
+            line = line,
+            indentation = indentation,
+            carets = carets,
+        )
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Diagnostic {
+        let check = match *error {
+            ParseError::ReversedRange { .. } => Check::ReversedRange,
+            _ => Check::InvalidEntry,
+        };
+        Diagnostic {
+            check,
+            message: error.message(),
+            position: error.position().clone(),
+        }
+    }
+}
+
+/// Every problem found in `input`, in the order they appear.
+pub fn lint(input: &str, options: &ParseOptions) -> Vec<Diagnostic> {
+    let (crontab, errors) = Crontab::parse(input, options);
+    let lines: Vec<&str> = input.lines().collect();
+    let mut diagnostics: Vec<Diagnostic> = errors.iter().map(Diagnostic::from).collect();
+
+    for entry in crontab.entries() {
+        let line = lines[entry.line - 1];
+        if let Trigger::Recurrence(ref recurrence) = entry.trigger {
+            let offset = entry.schedule.as_ptr() as usize - line.as_ptr() as usize;
+            lint_schedule(
+                entry.schedule,
+                offset,
+                entry.line,
+                recurrence,
+                options.dialect,
+                &mut diagnostics,
+            );
+        }
+        if let Some(index) = unescaped_percent(entry.raw_command) {
+            let start = entry.raw_command.as_ptr() as usize - line.as_ptr() as usize + index;
+            diagnostics.push(Diagnostic::new(
+                Check::UnescapedPercent,
+                "unescaped ‘%’ ends the command, and the rest is sent to its standard input".to_string(),
+                entry.line,
+                start..start + 1,
+            ));
+        }
+    }
+
+    if let Some(&last) = lines.last() {
+        let trimmed = last.trim_start();
+        if !input.ends_with('\n') && !trimmed.is_empty() && !trimmed.starts_with('#') {
+            diagnostics.push(Diagnostic::new(
+                Check::MissingNewline,
+                "missing newline at the end of the file, so cron may ignore this line".to_string(),
+                lines.len(),
+                last.len()..last.len(),
+            ));
+        }
+    }
+
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.position.line, diagnostic.position.span.start));
+    diagnostics
+}
+
+/// Checks the time fields in `schedule`, which starts `offset` bytes into its line.
+fn lint_schedule(
+    schedule: &str,
+    offset: usize,
+    line: usize,
+    recurrence: &Recurrence,
+    dialect: Dialect,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    // Nicknames can't have any of these problems
+    if schedule.starts_with('@') {
+        return;
+    }
+    let fields: Vec<(usize, &str)> = schedule
+        .split_whitespace()
+        .map(|field| (offset + field.as_ptr() as usize - schedule.as_ptr() as usize, field))
+        .collect();
+    let span = |first: usize, last: usize| fields[first].0..fields[last].0 + fields[last].1.len();
+
+    let mut names = FIELD_NAMES.to_vec();
+    if dialect.has_seconds() {
+        names.insert(0, SECOND_NAMES);
+    }
+    if dialect.has_year() {
+        names.push(YEAR_NAMES);
+    }
+    let first = if dialect.has_seconds() { 1 } else { 0 };
+    let (day_of_month, month, day_of_week) = (
+        first + DAY_OF_MONTH_FIELD,
+        first + MONTH_FIELD,
+        first + DAY_OF_WEEK_FIELD,
+    );
+
+    if !recurrence.is_satisfiable() {
+        diagnostics.push(Diagnostic::new(
+            Check::NeverRuns,
+            "never runs, since none of the months have any of these days".to_string(),
+            line,
+            span(day_of_month, if dialect.has_year() { month + 2 } else { month }),
+        ));
+    }
+    if dialect == Dialect::Vixie && !fields[day_of_month].1.starts_with('*') && !fields[day_of_week].1.starts_with('*')
+    {
+        diagnostics.push(Diagnostic::new(
+            Check::EitherDay,
+            "both day-of-month and day-of-week are restricted, so this runs on days matching either".to_string(),
+            line,
+            span(day_of_month, day_of_week),
+        ));
+    }
+
+    let parsers = DateTimeFieldParser::time_fields(dialect);
+    for (index, &(start, field)) in fields.iter().enumerate() {
+        let parser = &parsers[index];
+        let mut items = vec![];
+        let mut item_start = start;
+        for item in field.split(',') {
+            let values = parser.parse_field(item).unwrap_or_default();
+            items.push((item_start..item_start + item.len(), item, values));
+            item_start += item.len() + 1;
+        }
+
+        for &(ref item_span, item, _) in &items {
+            if let Some(message) = uneven_step(item, parser, names[index]) {
+                diagnostics.push(Diagnostic::new(Check::UnevenStep, message, line, item_span.clone()));
+            }
+        }
+
+        // Flag later items rather than earlier ones, so only one of a pair of duplicates is reported
+        let mut redundant = vec![false; items.len()];
+        for current in (0..items.len()).rev() {
+            // Days which depend on the month have no values of their own to compare
+            let covered = !items[current].2.is_empty()
+                && items[current].2.iter().all(|value| {
+                    items
+                        .iter()
+                        .enumerate()
+                        .any(|(other, item)| other != current && !redundant[other] && item.2.contains(value))
+                });
+            if covered {
+                redundant[current] = true;
+                diagnostics.push(Diagnostic::new(
+                    Check::RedundantValue,
+                    format!("‘{}’ is already covered by the rest of the list", items[current].1),
+                    line,
+                    items[current].0.clone(),
+                ));
+            }
+        }
+    }
+}
+
+/// Explains how the steps in `item` start over unevenly, if they do. Only steps which run to the end of the field and
+/// start before their second step would, such as `*/7` or `5-59/7`, repeat through the field, where any other range
+/// is a deliberate part of it.
+fn uneven_step(item: &str, parser: &DateTimeFieldParser, names: (&str, &str)) -> Option<String> {
+    if !item.contains('/') || DateTimeFieldParser::is_hashed(item) {
+        return None;
+    }
+    let (values, step) = parser.parse_stepped_range(item).ok()?;
+    let range = parser.range();
+    let size = range.end - range.start;
+    // Steps are capped at the end of the field when parsed
+    let step = step as u16;
+    if step <= 1 || step >= size || size.is_multiple_of(step) {
+        return None;
+    }
+    if values.end < range.end || values.start - range.start >= step {
+        return None;
+    }
+    let last = values.start + (values.end - 1 - values.start) / step * step;
+    Some(format!(
+        "step {} doesn't divide the {} {} evenly, so {} {} is followed by {} {}",
+        step, size, names.1, names.0, last, names.0, values.start
+    ))
+}
+
+/// Byte offset of the first `%` in `command` which isn't escaped with a backslash.
+fn unescaped_percent(command: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (index, character) in command.char_indices() {
+        match character {
+            '%' if !escaped => return Some(index),
+            '\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lint, unescaped_percent, Check, Diagnostic, Severity};
+    use crontab::{Dialect, ParseOptions, Position};
+    use test_support::user_options;
+
+    fn lint_user(input: &str) -> Vec<(Check, usize, ::std::ops::Range<usize>)> {
+        let options = user_options();
+        lint(input, &options)
+            .into_iter()
+            .map(|diagnostic| (diagnostic.check, diagnostic.position.line, diagnostic.position.span))
+            .collect()
+    }
+
+    #[test]
+    fn should_accept_clean_crontab() {
+        assert_eq!(
+            lint_user("# Comment\nMAILTO=ops\n*/15 9-17 * * 1-5 check\n@daily backup\n"),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn should_find_schedule_which_never_runs() {
+        assert_eq!(lint_user("0 0 30 2 * never\n"), vec![(Check::NeverRuns, 1, 4..8)]);
+    }
+
+    #[test]
+    fn should_find_uneven_step() {
+        assert_eq!(
+            lint_user("0 */6 * * * a\n*/7 * * * * b\n"),
+            vec![(Check::UnevenStep, 2, 0..3)]
+        );
+        assert_eq!(
+            lint_user("0-59/7 * * * * a\n5-59/7 * * * * b\n"),
+            vec![(Check::UnevenStep, 1, 0..6), (Check::UnevenStep, 2, 0..6)]
+        );
+    }
+
+    #[test]
+    fn should_accept_step_through_part_of_field() {
+        assert_eq!(
+            lint_user("3-59/5 * * * * a\n0-30/7 * * * * b\n30-59/7 * * * * c\n"),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn should_explain_uneven_step() {
+        let options = user_options();
+        assert_eq!(
+            lint("*/7 * * * * b\n", &options)[0].message,
+            "step 7 doesn't divide the 60 minutes evenly, so minute 56 is followed by minute 0"
+        );
+        assert_eq!(
+            lint("5-59/7 * * * * b\n", &options)[0].message,
+            "step 7 doesn't divide the 60 minutes evenly, so minute 54 is followed by minute 5"
+        );
+    }
+
+    #[test]
+    fn should_find_both_day_fields_restricted_in_vixie_dialect_only() {
+        assert_eq!(lint_user("0 0 13 * 5 friday\n"), vec![(Check::EitherDay, 1, 4..10)]);
+        let options = ParseOptions {
+            dialect: Dialect::Conjunctive,
+            ..user_options()
+        };
+        assert_eq!(lint("0 0 13 * 5 friday\n", &options), vec![]);
+    }
+
+    #[test]
+    fn should_not_report_days_depending_on_month_as_redundant() {
+        assert_eq!(lint_user("0 2 1,L,LW * * month-end\n"), vec![]);
+    }
+
+    #[test]
+    fn should_check_fields_at_their_place_in_quartz_dialect() {
+        let options = ParseOptions {
+            dialect: Dialect::Quartz,
+            ..user_options()
+        };
+        let checks: Vec<_> = lint("*/7 0 0 30 2 ? * never\n", &options)
+            .into_iter()
+            .map(|diagnostic| (diagnostic.check, diagnostic.position.span))
+            .collect();
+        assert_eq!(checks, vec![(Check::UnevenStep, 0..3), (Check::NeverRuns, 8..16)]);
+    }
+
+    #[test]
+    fn should_find_later_redundant_list_item() {
+        assert_eq!(
+            lint_user("*/15,30 1,1 * * * a\n"),
+            vec![(Check::RedundantValue, 1, 5..7), (Check::RedundantValue, 1, 10..11)]
+        );
+    }
+
+    #[test]
+    fn should_find_reversed_range_and_invalid_entry() {
+        assert_eq!(
+            lint_user("0 0 * * 5-1 a\n60 * * * * b\n"),
+            vec![(Check::ReversedRange, 1, 8..11), (Check::InvalidEntry, 2, 0..2)]
+        );
+    }
+
+    #[test]
+    fn should_find_unescaped_percent() {
+        assert_eq!(
+            lint_user("0 0 * * * date +\\%F\n0 0 * * * date +%F\n"),
+            vec![(Check::UnescapedPercent, 2, 16..17)]
+        );
+        assert_eq!(unescaped_percent("a\\\\%"), Some(3));
+    }
+
+    #[test]
+    fn should_find_missing_newline_after_last_entry() {
+        assert_eq!(
+            lint_user("0 0 * * * a\n0 1 * * * b"),
+            vec![(Check::MissingNewline, 2, 11..11)]
+        );
+        assert_eq!(lint_user("0 0 * * * a\n# End"), vec![]);
+    }
+
+    #[test]
+    fn should_render_like_rustc() {
+        let diagnostic = Diagnostic {
+            check: Check::UnevenStep,
+            message: "uneven".to_string(),
+            position: Position { line: 2, span: 2..5 },
+        };
+        assert_eq!(
+            diagnostic.render("crontab", "# Comment\n0\t*/7 * * * b\n"),
+            "warning[C003]: uneven\n --> crontab:2:3\n  |\n2 | 0\t*/7 * * * b\n  |  \t^^^\n"
+        );
+    }
+
+    #[test]
+    fn should_order_severities() {
+        assert!(Severity::Note < Severity::Warning && Severity::Warning < Severity::Error);
+        assert_eq!("warning".parse(), Ok(Severity::Warning));
+        assert!("fatal".parse::<Severity>().is_err());
+    }
+}
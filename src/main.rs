@@ -1,17 +1,436 @@
 extern crate chrono;
 extern crate cronlist;
+extern crate getopts;
+extern crate whoami;
 
-use chrono::Local;
+use chrono::{DateTime, Duration, Local, TimeZone, Utc};
+use cronlist::crontab::{CrontabKind, Dialect, Document, Entry};
+use cronlist::explain::explain;
+use cronlist::format::format;
+use cronlist::lint::{lint, Severity};
+use cronlist::output::{self, ics, Calendar, Format};
+use cronlist::run_parts::Expander;
+use cronlist::sources::{Source, Sources, Warning};
+use getopts::Options;
+use std::env;
+use std::fs;
 use std::io::{self, Read};
+use std::os::fd::AsFd;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+const PROGRAM_NAME: &str = "cronlist";
+const DEFAULT_ENTRIES: usize = 10;
+const EDIT_COMMAND: &str = "edit";
+const EXPLAIN_COMMAND: &str = "explain";
+const FORMAT_COMMAND: &str = "fmt";
+const LINT_COMMAND: &str = "lint";
+const SYSTEM_CRONTAB: &str = "/etc/crontab";
 
 fn main() {
-    let mut buffer = String::new();
+    let arguments: Vec<String> = env::args().skip(1).collect();
+
+    let mut options = Options::new();
+    options.optopt(
+        "f",
+        "from",
+        "list actions starting on or after DATETIME (default now)",
+        "DATETIME",
+    );
+    options.optopt("t", "to", "list actions starting on or before DATETIME", "DATETIME");
+    options.optopt("n", "entries", "stop after NUMBER actions (default 10)", "NUMBER");
+    options.optopt(
+        "l",
+        "last",
+        "list the last NUMBER actions up to --to (default now) instead",
+        "NUMBER",
+    );
+    options.optopt(
+        "o",
+        "output",
+        "print actions as text (default), json, jsonl, csv, tsv or ics",
+        "FORMAT",
+    );
+    options.optflag(
+        "r",
+        "recurring",
+        "with --output=ics, write one recurring event per entry where possible",
+    );
+    options.optopt(
+        "",
+        "fail-on",
+        "with lint, exit with status 1 on problems at least as severe as SEVERITY: note, warning (default) or error",
+        "SEVERITY",
+    );
+    options.optopt(
+        "d",
+        "dialect",
+        "read schedules as vixie (default), conjunctive, spring or quartz cron expressions",
+        "NAME",
+    );
+    options.optopt(
+        "",
+        "host-id",
+        "pick the values of H in schedules by hashing ID with the command (default the host name)",
+        "ID",
+    );
+    options.optopt(
+        "",
+        "disable",
+        "with edit, comment out the entry on line NUMBER",
+        "NUMBER",
+    );
+    options.optflag(
+        "i",
+        "in-place",
+        "with edit or fmt, write the result back to FILE instead of standard output",
+    );
+    options.optflag("s", "system", "show system crontabs only");
+    options.optflag("c", "crontab", "show your personal crontab only");
+    options.optflag("h", "help", "shows this help");
+
+    let matches = match options.parse(&arguments) {
+        Ok(matches) => matches,
+        Err(error) => die(&error.to_string()),
+    };
+    if matches.opt_present("help") {
+        usage(&options);
+    }
+    if matches.opt_present("system") && matches.opt_present("crontab") {
+        die("Can't choose both --system and --crontab");
+    }
+
+    let from = matches.opt_str("from").map(|datespec| datetime_from_date(&datespec));
+    let fail_on = match matches.opt_str("fail-on") {
+        Some(name) => name.parse::<Severity>().unwrap_or_else(|error| die(&error)),
+        None => Severity::Warning,
+    };
+    let dialect = match matches.opt_str("dialect") {
+        Some(name) => name.parse::<Dialect>().unwrap_or_else(|error| die(&error)),
+        None => Dialect::default(),
+    };
+    // The smallest step between runs, which `--from` and `--to` are widened by to include the runs at them
+    let precision = if dialect.has_seconds() {
+        Duration::seconds(1)
+    } else {
+        Duration::minutes(1)
+    };
+    // Read into by each command, with the settings for every crontab
+    let empty_sources = || Sources {
+        dialect,
+        host_id: matches.opt_str("host-id").or_else(|| whoami::fallible::hostname().ok()),
+        ..Sources::default()
+    };
+    let crontab_argument = || read_crontab(matches.free.get(1), matches.opt_present("system"), empty_sources());
+    match matches.free.first().map(String::as_str) {
+        None => {}
+        Some(EDIT_COMMAND) => edit_crontab(
+            &crontab_argument(),
+            matches.opt_str("disable"),
+            matches.opt_present("in-place"),
+        ),
+        Some(EXPLAIN_COMMAND) => explain_crontab(&crontab_argument(), &from.unwrap_or_else(Local::now), precision),
+        Some(FORMAT_COMMAND) => format_crontab(&crontab_argument(), matches.opt_present("in-place")),
+        Some(LINT_COMMAND) => lint_crontab(&crontab_argument(), fail_on),
+        Some(command) => die(&format!("Unknown command: {}", command)),
+    }
+    let to = matches.opt_str("to").map(|datespec| datetime_from_date(&datespec));
+    let entries = match matches.opt_str("entries") {
+        Some(count) => Some(
+            count
+                .parse::<usize>()
+                .unwrap_or_else(|_| die(&format!("Invalid entry count: {}", count))),
+        ),
+        None if to.is_none() => Some(DEFAULT_ENTRIES),
+        None => None,
+    };
+    let last = matches.opt_str("last").map(|count| {
+        count
+            .parse::<usize>()
+            .unwrap_or_else(|_| die(&format!("Invalid entry count: {}", count)))
+    });
+    if last.is_some() && matches.opt_present("entries") {
+        die("Can't choose both --entries and --last");
+    }
+    let format = match matches.opt_str("output") {
+        Some(ref name) if name != "text" => Some(name.parse::<Format>().unwrap_or_else(|error| die(&error))),
+        _ => None,
+    };
+    if matches.opt_present("recurring") && format != Some(Format::Ics) {
+        die("--recurring only applies to --output=ics");
+    }
+
+    let sources = read_crontabs(
+        !matches.opt_present("system"),
+        !matches.opt_present("crontab"),
+        empty_sources(),
+    );
+    let (crontab, warnings) = sources.crontab();
+    print_warnings(&sources.warnings);
+    print_warnings(&warnings);
+
+    let mut expander = Expander::new(Path::new("/"));
+    let rows = match last {
+        Some(count) => {
+            // Runs are listed strictly before the given time, but `--to` is inclusive
+            let to = to.unwrap_or_else(Local::now);
+            let mut rows = crontab
+                .runs_before(&(to + Duration::seconds(1)))
+                .take_while(|run| from.is_none_or(|from| run.datetime >= from))
+                .flat_map(|run| expander.expand(run).into_iter().rev())
+                .take(count)
+                .collect::<Vec<_>>();
+            rows.reverse();
+            rows
+        }
+        None => {
+            // Runs are listed strictly after the given time, but `--from` is inclusive
+            let from = from.unwrap_or_else(Local::now);
+            crontab
+                .runs_from(&(from - precision))
+                .take_while(|run| to.is_none_or(|to| run.datetime <= to))
+                .flat_map(|run| expander.expand(run))
+                .take(entries.unwrap_or(usize::MAX))
+                .collect::<Vec<_>>()
+        }
+    };
+    if let Some(format) = format {
+        let stdout = io::stdout();
+        let written = match format {
+            Format::Ics => Calendar {
+                recurring: matches.opt_present("recurring"),
+                local_zone: ics::local_zone(),
+                ..Calendar::new(Utc::now())
+            }
+            .write(&rows, stdout.lock()),
+            _ => output::write(format, &rows, stdout.lock()),
+        };
+        if let Err(error) = written {
+            die(&format!("Could not write output: {}", error));
+        }
+        print_warnings(&expander.warnings);
+        return;
+    }
+    let time_format = if dialect.has_seconds() {
+        "%Y-%m-%d %k:%M:%S"
+    } else {
+        "%Y-%m-%d %k:%M"
+    };
+    for row in rows {
+        let command = match row.script {
+            Some(ref script) => script.display().to_string(),
+            None => row.run.entry.command.to_string(),
+        };
+        println!(
+            "{}  {}  {}",
+            row.run.datetime.format(time_format),
+            row.run.entry.user,
+            with_stdin(&command, row.run.entry)
+        );
+    }
+
+    let mut reboot_entries = crontab.reboot_entries().peekable();
+    if reboot_entries.peek().is_some() {
+        println!("\nAt boot:");
+        for entry in reboot_entries {
+            let scripts = expander.scripts(entry);
+            if scripts.is_empty() {
+                println!("{}  {}", entry.user, with_stdin(&entry.command, entry));
+            }
+            for script in scripts {
+                println!("{}  {}", entry.user, with_stdin(&script.display().to_string(), entry));
+            }
+        }
+    }
+
+    print_warnings(&expander.warnings);
+}
+
+/// `command` followed by the standard input of `entry`, if any, quoted with escapes for newlines.
+fn with_stdin(command: &str, entry: &Entry) -> String {
+    match entry.stdin {
+        Some(ref stdin) => format!("{}  stdin: {:?}", command, stdin),
+        None => command.to_string(),
+    }
+}
+
+fn print_warnings(warnings: &[Warning]) {
+    for warning in warnings {
+        eprintln!("{}: {}", PROGRAM_NAME, warning);
+    }
+}
+
+/// Standard input if it's piped or redirected, otherwise the system crontabs, the spool and `crontab -l`, added to
+/// `sources`.
+fn read_crontabs(user: bool, system: bool, mut sources: Sources) -> Sources {
+    if stdin_is_piped() {
+        sources.sources.push(Source {
+            path: None,
+            name: "standard input".to_string(),
+            contents: read_stdin(),
+            kind: CrontabKind::User,
+            user: None,
+            dialect: sources.dialect,
+            host_id: sources.host_id.clone(),
+        });
+        return sources;
+    }
+
+    let root = Path::new("/");
+    if system {
+        sources.add_system_crontabs(root);
+    }
+    if user && system {
+        sources.add_spool_crontabs(root);
+    }
+    if user {
+        sources.add_user_crontab();
+    }
+    sources
+}
+
+/// The crontab at `path`, with the settings of `sources`. Without a path this reads standard input if it's piped or
+/// redirected, otherwise `/etc/crontab` with `--system` or the output of `crontab -l`.
+fn read_crontab(path: Option<&String>, system: bool, mut sources: Sources) -> Source {
+    let kind = if system { CrontabKind::System } else { CrontabKind::User };
+    let (name, contents) = match path {
+        Some(path) => (path.to_string(), read_file(path)),
+        None if stdin_is_piped() => ("standard input".to_string(), read_stdin()),
+        None if system => (SYSTEM_CRONTAB.to_string(), read_file(SYSTEM_CRONTAB)),
+        None => {
+            sources.add_user_crontab();
+            return sources.sources.pop().unwrap_or_else(|| die("You don't have a crontab"));
+        }
+    };
+    Source {
+        path: path.map(PathBuf::from),
+        name,
+        contents,
+        kind,
+        user: None,
+        dialect: sources.dialect,
+        host_id: sources.host_id,
+    }
+}
+
+/// Comments out line `disable` of `source`, printing the result or with `in_place` writing it back to its file.
+fn edit_crontab(source: &Source, disable: Option<String>, in_place: bool) -> ! {
+    let line = match disable {
+        Some(line) => line
+            .parse::<usize>()
+            .unwrap_or_else(|_| die(&format!("Invalid line number: {}", line))),
+        None => die("Nothing to edit, use --disable"),
+    };
+    let mut document = Document::parse(&source.contents);
+    if let Err(error) = document.disable(line) {
+        die(&format!("Could not edit {}: {}", source.name, error));
+    }
+    write_crontab(source, &document.to_string(), in_place);
+}
+
+/// Prints `source` with its schedules in canonical form, or with `in_place` writes it back to its file.
+fn format_crontab(source: &Source, in_place: bool) -> ! {
+    match format(&source.contents, &source.parse_options()) {
+        Ok(formatted) => write_crontab(source, &formatted, in_place),
+        Err(error) => die(&format!("Could not format {}: {}", source.name, error)),
+    }
+}
+
+/// Prints `contents`, or with `in_place` writes it to the file `source` was read from.
+fn write_crontab(source: &Source, contents: &str, in_place: bool) -> ! {
+    match (in_place, &source.path) {
+        (false, _) => print!("{}", contents),
+        (true, Some(path)) => fs::write(path, contents)
+            .unwrap_or_else(|error| die(&format!("Could not write {}: {}", path.display(), error))),
+        (true, None) => die("--in-place needs a FILE"),
+    }
+    process::exit(0);
+}
+
+/// Prints `source` with a description of each entry.
+fn explain_crontab(source: &Source, from: &DateTime<Local>, precision: Duration) -> ! {
+    // Runs are listed strictly after the given time, but `--from` is inclusive
+    print!(
+        "{}",
+        explain(&source.contents, &source.parse_options(), &(*from - precision))
+    );
+    process::exit(0);
+}
+
+/// Prints the problems in `source`, failing if any of them is at least as severe as `fail_on`.
+fn lint_crontab(source: &Source, fail_on: Severity) -> ! {
+    let diagnostics = lint(&source.contents, &source.parse_options());
+    let rendered: Vec<String> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.render(&source.name, &source.contents))
+        .collect();
+    print!("{}", rendered.join("\n"));
+    if diagnostics.iter().any(|diagnostic| diagnostic.severity() >= fail_on) {
+        process::exit(1);
+    }
+    process::exit(0);
+}
+
+fn read_file(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|error| die(&format!("Could not read {}: {}", path, error)))
+}
+
+/// Whether standard input is a pipe or a file, rather than a terminal, a device such as `/dev/null` or closed, as when
+/// run from cron or over SSH without a terminal.
+fn stdin_is_piped() -> bool {
     io::stdin()
-        .read_to_string(&mut buffer)
-        .expect("Could not read standard input");
+        .as_fd()
+        .try_clone_to_owned()
+        .map(fs::File::from)
+        .and_then(|file| file.metadata())
+        .map(|metadata| metadata.file_type().is_fifo() || metadata.file_type().is_file())
+        .unwrap_or(false)
+}
 
-    let crontab = cronlist::crontab::Crontab::new(&buffer);
-    let next_run = crontab.next_run(Local::now().naive_local());
+fn read_stdin() -> String {
+    let mut buffer = String::new();
+    if let Err(error) = io::stdin().lock().read_to_string(&mut buffer) {
+        die(&format!("Could not read standard input: {}", error));
+    }
+    buffer
+}
+
+/// Interprets `datespec` the same way as `date --date`.
+fn datetime_from_date(datespec: &str) -> DateTime<Local> {
+    let command = format!("date -d \"{}\" '+%s'", datespec);
+    let output = Command::new("date")
+        .arg("-d")
+        .arg(datespec)
+        .arg("+%s")
+        .output()
+        .unwrap_or_else(|_| die(&format!("command ‘{}’ failed", command)));
+    let timestamp = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse::<i64>()
+        .unwrap_or_else(|_| die(&format!("command ‘{}’ didn't return a meaningful value", command)));
+    match Local.timestamp_opt(timestamp, 0).single() {
+        Some(datetime) => datetime,
+        None => die(&format!("date ‘{}’ not supported", datespec)),
+    }
+}
+
+fn usage(options: &Options) -> ! {
+    let brief = format!(
+        "Usage: {0} [options]\n       {0} edit --disable=NUMBER [options] [FILE]\n       {0} explain [options] [FILE]\n       {0} fmt [options] [FILE]\n       {0} lint [options] [FILE]\n\n\
+         {0} lists upcoming cron actions from the system crontabs,\n  every user crontab it can read and your personal crontab.\n\
+         {0} edit changes a crontab, leaving the rest of it as written.\n\
+         {0} explain prints a crontab with a comment above each entry\n  describing when it runs.\n\
+         {0} fmt rewrites the schedules of a crontab in their shortest form\n  and aligns the columns.\n\
+         {0} lint reports likely mistakes in a crontab.",
+        PROGRAM_NAME
+    );
+    println!("{}", options.usage(&brief));
+    println!("  DATETIME should be a date expression that can be passed to date(1).");
+    process::exit(0);
+}
 
-    println!("{} {}", next_run.datetime, next_run.entry.command);
+fn die(message: &str) -> ! {
+    eprintln!("{}: {}", PROGRAM_NAME, message);
+    process::exit(1);
 }
@@ -0,0 +1,423 @@
+//! iCalendar (RFC 5545) listings of runs, for subscribing to cron schedules in calendar applications.
+
+use chrono::{DateTime, Duration, FixedOffset, Offset, TimeZone, Utc};
+use chrono_tz::{OffsetComponents, OffsetName, Tz};
+use crontab::{Entry, Trigger};
+use hash::fnv1a;
+use iana_time_zone;
+use run_parts::Row;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+
+const PRODUCT_ID: &str = "-//cronlist//cronlist//EN";
+const UID_DOMAIN: &str = "cronlist";
+/// Content lines longer than this many bytes are folded.
+const MAX_LINE_LENGTH: usize = 75;
+const UTC_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+const LOCAL_FORMAT: &str = "%Y%m%dT%H%M%S";
+
+/// Options for writing a calendar.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Calendar {
+    /// When the calendar was created, used as the `DTSTAMP` of every event.
+    pub stamp: DateTime<Utc>,
+    /// Whether to write one recurring event per entry and script when its runs can be expressed exactly as an
+    /// `RRULE`, rather than one event per run.
+    pub recurring: bool,
+    /// The zone the runs were listed in, which entries without `CRON_TZ` are scheduled in. Entries without a zone
+    /// are always written as separate events.
+    pub local_zone: Option<Tz>,
+}
+
+impl Calendar {
+    pub fn new(stamp: DateTime<Utc>) -> Calendar {
+        Calendar {
+            stamp,
+            recurring: false,
+            local_zone: None,
+        }
+    }
+
+    /// Writes a `VCALENDAR` with an event per row, or with recurring events for the rows of each entry and script.
+    /// Recurring events stop at the last of their rows, so the calendar covers the same runs either way.
+    pub fn write<W: Write, Zone: TimeZone>(&self, rows: &[Row<Zone>], mut writer: W) -> io::Result<()> {
+        let mut events = vec![];
+        let mut zones: Vec<(Tz, DateTime<Utc>, DateTime<Utc>)> = vec![];
+
+        if self.recurring {
+            for group in Calendar::group(rows) {
+                match self.recurring_event(&group) {
+                    Some((zone, lines)) => {
+                        let first = group[0].run.datetime.with_timezone(&Utc);
+                        let last = group[group.len() - 1].run.datetime.with_timezone(&Utc);
+                        match zones.iter_mut().find(|&&mut (known, _, _)| known == zone) {
+                            Some(&mut (_, ref mut start, ref mut end)) => {
+                                *start = (*start).min(first);
+                                *end = (*end).max(last);
+                            }
+                            None => zones.push((zone, first, last)),
+                        }
+                        events.extend(lines);
+                    }
+                    None => {
+                        for row in group {
+                            events.extend(self.single_event(row));
+                        }
+                    }
+                }
+            }
+        } else {
+            for row in rows {
+                events.extend(self.single_event(row));
+            }
+        }
+
+        let mut lines = vec![
+            "BEGIN:VCALENDAR".to_string(),
+            "VERSION:2.0".to_string(),
+            format!("PRODID:{}", PRODUCT_ID),
+        ];
+        for (zone, start, end) in zones {
+            lines.extend(timezone(zone, start, end));
+        }
+        lines.extend(events);
+        lines.push("END:VCALENDAR".to_string());
+
+        for line in lines {
+            write!(writer, "{}\r\n", fold(&line))?;
+        }
+        Ok(())
+    }
+
+    /// Rows with the same entry and script, in order of their first run.
+    fn group<'r, 'a, Zone: TimeZone>(rows: &'r [Row<'a, Zone>]) -> Vec<Vec<&'r Row<'a, Zone>>> {
+        let mut groups: Vec<Vec<&Row<Zone>>> = vec![];
+        let mut indexes: HashMap<(*const Entry, Option<&Path>), usize> = HashMap::new();
+        for row in rows {
+            let key = (row.run.entry as *const Entry, row.script.as_deref());
+            let index = *indexes.entry(key).or_insert_with(|| {
+                groups.push(vec![]);
+                groups.len() - 1
+            });
+            groups[index].push(row);
+        }
+        groups
+    }
+
+    fn single_event<Zone: TimeZone>(&self, row: &Row<Zone>) -> Vec<String> {
+        let start = row.run.datetime.with_timezone(&Utc);
+        vec![
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}", uid(row, &start)),
+            format!("DTSTAMP:{}", self.stamp.format(UTC_FORMAT)),
+            format!("DTSTART:{}", start.format(UTC_FORMAT)),
+            format!("SUMMARY:{}", escape(&summary(row))),
+            "END:VEVENT".to_string(),
+        ]
+    }
+
+    /// A recurring event for the rows of one entry and script, if an `RRULE` in the entry's zone gives exactly the
+    /// same runs. It doesn't when the day fields are alternatives or a clock change moved, skipped or repeated a run.
+    fn recurring_event<Zone: TimeZone>(&self, group: &[&Row<Zone>]) -> Option<(Tz, Vec<String>)> {
+        let entry = group[0].run.entry;
+        let recurrence = match entry.trigger {
+            Trigger::Recurrence(ref recurrence) => recurrence,
+            Trigger::Reboot => return None,
+        };
+        let rrule = recurrence.rrule()?;
+        let zone = entry.timezone.or(self.local_zone)?;
+        // Each run follows the last by the local clock, unless a clock change skipped or repeated some
+        let locals: Vec<_> = group
+            .iter()
+            .map(|row| row.run.datetime.with_timezone(&zone).naive_local())
+            .collect();
+        let exact = recurrence.matches(locals[0])
+            && locals
+                .windows(2)
+                .all(|pair| recurrence.next_match(pair[0]) == Some(pair[1]));
+        if !exact {
+            return None;
+        }
+
+        let first = group[0].run.datetime.with_timezone(&Utc);
+        let last = group[group.len() - 1].run.datetime.with_timezone(&Utc);
+        let lines = vec![
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}", uid(group[0], &first)),
+            format!("DTSTAMP:{}", self.stamp.format(UTC_FORMAT)),
+            format!(
+                "DTSTART;TZID={}:{}",
+                zone.name(),
+                first.with_timezone(&zone).format(LOCAL_FORMAT)
+            ),
+            format!("RRULE:{};UNTIL={}", rrule, last.format(UTC_FORMAT)),
+            format!("SUMMARY:{}", escape(&summary(group[0]))),
+            "END:VEVENT".to_string(),
+        ];
+        Some((zone, lines))
+    }
+}
+
+/// The zone of the system clock, if it has a name in the time zone database.
+pub fn local_zone() -> Option<Tz> {
+    iana_time_zone::get_timezone().ok()?.parse().ok()
+}
+
+fn summary<Zone: TimeZone>(row: &Row<Zone>) -> String {
+    match row.script {
+        Some(ref script) => script.display().to_string(),
+        None => row.run.entry.command.to_string(),
+    }
+}
+
+/// A stable identifier from where the entry is, the script and when it runs, so subscriptions update events in place.
+fn uid<Zone: TimeZone>(row: &Row<Zone>, start: &DateTime<Utc>) -> String {
+    let entry = row.run.entry;
+    let key = format!(
+        "{}\0{}\0{}\0{}",
+        entry
+            .source
+            .map(|source| source.display().to_string())
+            .unwrap_or_default(),
+        entry.line,
+        row.script
+            .as_ref()
+            .map(|script| script.display().to_string())
+            .unwrap_or_default(),
+        start.format(UTC_FORMAT)
+    );
+    format!(
+        "{}-{:016x}@{}",
+        start.format(UTC_FORMAT),
+        fnv1a(key.bytes()),
+        UID_DOMAIN
+    )
+}
+
+/// A `VTIMEZONE` with every offset `zone` uses between `start` and `end`.
+fn timezone(zone: Tz, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<String> {
+    let mut lines = vec!["BEGIN:VTIMEZONE".to_string(), format!("TZID:{}", zone.name())];
+    let mut current = start.with_timezone(&zone);
+    lines.extend(observance(&current, current.offset().fix()));
+
+    while current < end {
+        let next = current + Duration::hours(1);
+        if next.offset().fix() != current.offset().fix()
+            || next.offset().abbreviation() != current.offset().abbreviation()
+        {
+            // Clock changes happen on the second, so this finds the first second of the new offset
+            let (mut before, mut after) = (current.timestamp(), next.timestamp());
+            while after - before > 1 {
+                let middle = (before + after) / 2;
+                let offset = zone.timestamp_opt(middle, 0).unwrap();
+                if offset.offset().fix() == current.offset().fix()
+                    && offset.offset().abbreviation() == current.offset().abbreviation()
+                {
+                    before = middle;
+                } else {
+                    after = middle;
+                }
+            }
+            let change = zone.timestamp_opt(after, 0).unwrap();
+            lines.extend(observance(&change, current.offset().fix()));
+        }
+        current = next;
+    }
+
+    lines.push("END:VTIMEZONE".to_string());
+    lines
+}
+
+/// The observance starting at `start`, with `from` the offset before it.
+fn observance(start: &DateTime<Tz>, from: FixedOffset) -> Vec<String> {
+    let offset = start.offset();
+    let kind = if offset.dst_offset().is_zero() {
+        "STANDARD"
+    } else {
+        "DAYLIGHT"
+    };
+    let mut lines = vec![
+        format!("BEGIN:{}", kind),
+        format!(
+            "DTSTART:{}",
+            start.with_timezone(&from).naive_local().format(LOCAL_FORMAT)
+        ),
+        format!("TZOFFSETFROM:{}", utc_offset(from)),
+        format!("TZOFFSETTO:{}", utc_offset(offset.fix())),
+    ];
+    lines.extend(offset.abbreviation().map(|name| format!("TZNAME:{}", name)));
+    lines.push(format!("END:{}", kind));
+    lines
+}
+
+fn utc_offset(offset: FixedOffset) -> String {
+    let seconds = offset.local_minus_utc();
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let seconds = seconds.abs();
+    let mut formatted = format!("{}{:02}{:02}", sign, seconds / 3600, seconds / 60 % 60);
+    if seconds % 60 != 0 {
+        formatted.push_str(&format!("{:02}", seconds % 60));
+    }
+    formatted
+}
+
+/// Escapes a `TEXT` value.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Splits `line` into lines of at most `MAX_LINE_LENGTH` bytes, continuing each with a space, without splitting
+/// characters.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut length = 0;
+    for character in line.chars() {
+        if length + character.len_utf8() > MAX_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(character);
+        length += character.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, fold, utc_offset, Calendar};
+    use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+    use chrono_tz::Pacific::Auckland;
+    use chrono_tz::Tz;
+    use crontab::Crontab;
+    use run_parts::Row;
+
+    fn calendar(crontab: &str, from: DateTime<Tz>, count: usize, recurring: bool) -> String {
+        let crontab = Crontab::new(crontab).unwrap();
+        let rows: Vec<_> = crontab
+            .runs_from(&from)
+            .take(count)
+            .map(|run| Row { run, script: None })
+            .collect();
+        let calendar = Calendar {
+            stamp: Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap(),
+            recurring,
+            local_zone: Some(Auckland),
+        };
+        let mut buffer = vec![];
+        calendar.write(&rows, &mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    fn events(calendar: &str) -> Vec<&str> {
+        calendar.split("BEGIN:VEVENT\r\n").skip(1).collect()
+    }
+
+    #[test]
+    fn should_write_event_per_run() {
+        let from = Auckland.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let actual = calendar("30 6 * * * backup --all", from, 2, false);
+        assert!(actual.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//cronlist//cronlist//EN\r\n"));
+        assert!(actual.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+        let events = events(&actual);
+        assert_eq!(events.len(), 2);
+        assert!(events[0].starts_with("UID:20191231T173000Z-"));
+        assert!(events[0].ends_with(
+            "@cronlist\r\nDTSTAMP:20200101T000000Z\r\nDTSTART:20191231T173000Z\r\nSUMMARY:backup --all\r\n\
+             END:VEVENT\r\n"
+        ));
+        assert!(events[1].contains("DTSTART:20200101T173000Z\r\n"));
+    }
+
+    #[test]
+    fn should_derive_different_uids_from_source_line_and_time() {
+        let from = Auckland.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let actual = calendar("30 6 * * * first\n30 6 * * * second", from, 4, false);
+        let uids: Vec<_> = actual.lines().filter(|line| line.starts_with("UID:")).collect();
+        assert_eq!(uids.len(), 4);
+        for (index, uid) in uids.iter().enumerate() {
+            assert!(!uids[index + 1..].contains(uid));
+        }
+        assert_eq!(actual, calendar("30 6 * * * first\n30 6 * * * second", from, 4, false));
+    }
+
+    #[test]
+    fn should_write_recurring_event_per_entry() {
+        let from = Auckland.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let actual = calendar("30 6 * * 1-5 report", from, 5, true).replace("\r\n ", "");
+        let events = events(&actual);
+        assert_eq!(events.len(), 1);
+        assert!(events[0].contains(
+            "DTSTART;TZID=Pacific/Auckland:20200101T063000\r\n\
+             RRULE:FREQ=DAILY;BYDAY=MO,TU,WE,TH,FR;BYHOUR=6;BYMINUTE=30;UNTIL=20200106T173000Z\r\n\
+             SUMMARY:report\r\n"
+        ));
+        assert!(actual.contains(
+            "BEGIN:VTIMEZONE\r\nTZID:Pacific/Auckland\r\nBEGIN:DAYLIGHT\r\nDTSTART:20200101T063000\r\n\
+             TZOFFSETFROM:+1300\r\nTZOFFSETTO:+1300\r\nTZNAME:NZDT\r\nEND:DAYLIGHT\r\nEND:VTIMEZONE\r\n"
+        ));
+    }
+
+    #[test]
+    fn should_include_clock_changes_in_time_zone() {
+        let from = Auckland.with_ymd_and_hms(2020, 4, 1, 0, 0, 0).unwrap();
+        let actual = calendar("0 12 * * * lunch", from, 10, true);
+        assert_eq!(events(&actual).len(), 1);
+        assert!(actual.contains(
+            "BEGIN:STANDARD\r\nDTSTART:20200405T030000\r\nTZOFFSETFROM:+1300\r\nTZOFFSETTO:+1200\r\n\
+             TZNAME:NZST\r\nEND:STANDARD\r\n"
+        ));
+    }
+
+    #[test]
+    fn should_write_event_per_run_when_clock_change_moves_run() {
+        // 02:30 doesn't exist on 2020-09-27 in Auckland, so cron runs at 03:00 instead
+        let from = Auckland.with_ymd_and_hms(2020, 9, 26, 0, 0, 0).unwrap();
+        let actual = calendar("30 2 * * * backup", from, 3, true);
+        assert_eq!(events(&actual).len(), 3);
+        assert!(!actual.contains("RRULE"));
+        assert!(!actual.contains("VTIMEZONE"));
+    }
+
+    #[test]
+    fn should_write_event_per_run_when_clock_change_repeats_run() {
+        // 02:00 through 02:59 happen twice on 2020-04-05 in Auckland, and cron runs wildcard hours both times
+        let from = Auckland.with_ymd_and_hms(2020, 4, 5, 1, 0, 0).unwrap();
+        let actual = calendar("30 * * * * check", from, 4, true);
+        assert_eq!(events(&actual).len(), 4);
+        assert!(!actual.contains("RRULE"));
+    }
+
+    #[test]
+    fn should_write_event_per_run_when_either_day_field_matches() {
+        let from = Auckland.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let actual = calendar("0 0 13 * 5 superstition", from, 3, true);
+        assert_eq!(events(&actual).len(), 3);
+        assert!(!actual.contains("RRULE"));
+    }
+
+    #[test]
+    fn should_escape_text() {
+        assert_eq!(escape("a; b, c\\d\ne"), "a\\; b\\, c\\\\d\\ne");
+    }
+
+    #[test]
+    fn should_fold_long_lines_between_characters() {
+        let line = format!("SUMMARY:{}", "é".repeat(40));
+        let folded = fold(&line);
+        let lines: Vec<_> = folded.split("\r\n").collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.len() <= 75));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+
+    #[test]
+    fn should_format_utc_offsets() {
+        assert_eq!(utc_offset(FixedOffset::east_opt(5 * 3600 + 45 * 60).unwrap()), "+0545");
+        assert_eq!(utc_offset(FixedOffset::west_opt(3 * 3600 + 30 * 60).unwrap()), "-0330");
+        assert_eq!(utc_offset(FixedOffset::east_opt(0).unwrap()), "+0000");
+    }
+}
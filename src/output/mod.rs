@@ -0,0 +1,282 @@
+//! Machine-readable listings of runs.
+
+pub use self::ics::Calendar;
+
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use csv;
+use run_parts::Row;
+use serde_json;
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+pub mod ics;
+
+const TSV_HEADER: [&str; 9] = [
+    "timestamp",
+    "user",
+    "command",
+    "stdin",
+    "script",
+    "source",
+    "line",
+    "schedule",
+    "hashed_schedule",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// An array of objects.
+    Json,
+    /// One object per line.
+    Jsonl,
+    /// RFC 4180, with a header line.
+    Csv,
+    /// Tab-separated, with a header line and backslash escapes for tabs, newlines and backslashes.
+    Tsv,
+    /// An iCalendar with an event per run.
+    Ics,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Format, String> {
+        match name {
+            "json" => Ok(Format::Json),
+            "jsonl" => Ok(Format::Jsonl),
+            "csv" => Ok(Format::Csv),
+            "tsv" => Ok(Format::Tsv),
+            "ics" => Ok(Format::Ics),
+            _ => Err(format!("unknown output format ‘{}’", name)),
+        }
+    }
+}
+
+/// One run, as it's serialized.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Record<'a> {
+    /// RFC 3339 with the offset of the listing's zone.
+    pub timestamp: String,
+    pub user: &'a str,
+    pub command: &'a str,
+    /// What cron writes to the command's standard input, if anything.
+    pub stdin: Option<&'a str>,
+    /// The script `run-parts` runs, if the run was expanded.
+    pub script: Option<String>,
+    /// The file the entry is in, if it was read from one.
+    pub source: Option<String>,
+    pub line: usize,
+    pub schedule: &'a str,
+    /// The schedule with each `H` replaced by the values it stands for, if it has any.
+    pub hashed_schedule: Option<&'a str>,
+}
+
+impl<'a> Record<'a> {
+    pub fn new<Zone: TimeZone>(row: &'a Row<'a, Zone>) -> Record<'a>
+    where
+        Zone::Offset: Display,
+    {
+        let entry = row.run.entry;
+        Record {
+            timestamp: Record::timestamp(&row.run.datetime),
+            user: &entry.user,
+            command: &entry.command,
+            stdin: entry.stdin.as_deref(),
+            script: row.script.as_ref().map(|script| script.display().to_string()),
+            source: entry.source.map(|source| source.display().to_string()),
+            line: entry.line,
+            schedule: entry.schedule,
+            hashed_schedule: entry.hashed_schedule.as_deref(),
+        }
+    }
+
+    fn timestamp<Zone: TimeZone>(datetime: &DateTime<Zone>) -> String
+    where
+        Zone::Offset: Display,
+    {
+        datetime.to_rfc3339_opts(SecondsFormat::Secs, false)
+    }
+
+    fn tsv_fields(&self) -> Vec<String> {
+        let line = self.line.to_string();
+        [
+            &self.timestamp[..],
+            self.user,
+            self.command,
+            self.stdin.unwrap_or(""),
+            self.script.as_ref().map_or("", String::as_str),
+            self.source.as_ref().map_or("", String::as_str),
+            &line,
+            self.schedule,
+            self.hashed_schedule.unwrap_or(""),
+        ]
+        .iter()
+        .map(|field| escape_tsv(field))
+        .collect()
+    }
+}
+
+/// Writes `rows` in one of the machine-readable formats.
+pub fn write<W: Write, Zone: TimeZone>(format: Format, rows: &[Row<Zone>], mut writer: W) -> io::Result<()>
+where
+    Zone::Offset: Display,
+{
+    let records: Vec<Record> = rows.iter().map(Record::new).collect();
+    match format {
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut writer, &records)?;
+            writeln!(writer)
+        }
+        Format::Jsonl => {
+            for record in &records {
+                serde_json::to_writer(&mut writer, record)?;
+                writeln!(writer)?;
+            }
+            Ok(())
+        }
+        Format::Csv => {
+            let mut csv_writer = csv::Writer::from_writer(writer);
+            for record in &records {
+                csv_writer.serialize(record)?;
+            }
+            csv_writer.flush()
+        }
+        Format::Tsv => {
+            writeln!(writer, "{}", TSV_HEADER.join("\t"))?;
+            for record in &records {
+                writeln!(writer, "{}", record.tsv_fields().join("\t"))?;
+            }
+            Ok(())
+        }
+        Format::Ics => Calendar::new(Utc::now()).write(rows, writer),
+    }
+}
+
+fn escape_tsv(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write, Format, Record};
+    use chrono::{FixedOffset, TimeZone};
+    use crontab::{Crontab, CrontabKind, ParseOptions};
+    use run_parts::Row;
+    use std::path::{Path, PathBuf};
+
+    const CRONTAB: &str = "# Reports\n30 6 * * 1-5 root report --to \"ops, dev\" --date=$(date +\\%F)%weekly%summary\n";
+
+    fn output(format: Format) -> String {
+        let options = ParseOptions {
+            kind: CrontabKind::System,
+            ..ParseOptions::default()
+        };
+        let (mut crontab, errors) = Crontab::parse(CRONTAB, &options);
+        assert!(errors.is_empty());
+        crontab.set_source(Path::new("/etc/cron.d/reports"));
+        let from = FixedOffset::east_opt(13 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2020, 1, 1, 0, 0, 0)
+            .unwrap();
+        let rows: Vec<_> = crontab
+            .runs_from(&from)
+            .take(2)
+            .map(|run| Row { run, script: None })
+            .collect();
+        let mut buffer = vec![];
+        write(format, &rows, &mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn should_parse_format_names() {
+        assert_eq!("jsonl".parse(), Ok(Format::Jsonl));
+        assert_eq!("xml".parse::<Format>(), Err("unknown output format ‘xml’".to_string()));
+    }
+
+    #[test]
+    fn should_build_record_from_row() {
+        let crontab = Crontab::new("  @daily run-parts /etc/cron.daily").unwrap();
+        let from = FixedOffset::west_opt(5 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2020, 1, 1, 12, 0, 0)
+            .unwrap();
+        let row = Row {
+            run: crontab.next_run(&from).unwrap(),
+            script: Some(PathBuf::from("/etc/cron.daily/logrotate")),
+        };
+        assert_eq!(
+            Record::new(&row),
+            Record {
+                timestamp: "2020-01-02T00:00:00-05:00".to_string(),
+                user: &row.run.entry.user,
+                command: "run-parts /etc/cron.daily",
+                stdin: None,
+                script: Some("/etc/cron.daily/logrotate".to_string()),
+                source: None,
+                line: 1,
+                schedule: "@daily",
+                hashed_schedule: None,
+            }
+        );
+    }
+
+    #[test]
+    fn should_write_json_array() {
+        let actual = output(Format::Json);
+        assert!(actual.starts_with("[\n  {\n    \"timestamp\": \"2020-01-01T06:30:00+13:00\",\n"));
+        assert!(actual.contains("\"command\": \"report --to \\\"ops, dev\\\" --date=$(date +%F)\",\n"));
+        assert!(actual.contains("\"stdin\": \"weekly\\nsummary\",\n"));
+        assert!(actual.contains("\"script\": null,\n"));
+        assert!(actual.contains("\"source\": \"/etc/cron.d/reports\",\n"));
+        assert!(actual.ends_with("\"schedule\": \"30 6 * * 1-5\",\n    \"hashed_schedule\": null\n  }\n]\n"));
+    }
+
+    #[test]
+    fn should_write_one_json_object_per_line() {
+        let actual = output(Format::Jsonl);
+        let lines: Vec<_> = actual.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[1],
+            "{\"timestamp\":\"2020-01-02T06:30:00+13:00\",\"user\":\"root\",\
+             \"command\":\"report --to \\\"ops, dev\\\" --date=$(date +%F)\",\"stdin\":\"weekly\\nsummary\",\
+             \"script\":null,\
+             \"source\":\"/etc/cron.d/reports\",\"line\":2,\"schedule\":\"30 6 * * 1-5\",\"hashed_schedule\":null}"
+        );
+    }
+
+    #[test]
+    fn should_quote_csv_fields() {
+        assert_eq!(
+            output(Format::Csv).lines().take(3).collect::<Vec<_>>(),
+            vec![
+                "timestamp,user,command,stdin,script,source,line,schedule,hashed_schedule",
+                "2020-01-01T06:30:00+13:00,root,\"report --to \"\"ops, dev\"\" --date=$(date +%F)\",\"weekly",
+                "summary\",,/etc/cron.d/reports,2,30 6 * * 1-5,",
+            ]
+        );
+    }
+
+    #[test]
+    fn should_write_tsv_without_quoting() {
+        assert_eq!(
+            output(Format::Tsv).lines().take(2).collect::<Vec<_>>(),
+            vec![
+                "timestamp\tuser\tcommand\tstdin\tscript\tsource\tline\tschedule\thashed_schedule",
+                "2020-01-01T06:30:00+13:00\troot\treport --to \"ops, dev\" --date=$(date +%F)\tweekly\\nsummary\t\t\
+                 /etc/cron.d/reports\t2\t30 6 * * 1-5\t",
+            ]
+        );
+    }
+
+    #[test]
+    fn should_escape_tsv_separators() {
+        assert_eq!(super::escape_tsv("a\tb\nc\\d"), "a\\tb\\nc\\\\d");
+    }
+}
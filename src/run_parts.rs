@@ -0,0 +1,215 @@
+//! Finding the scripts behind `run-parts` invocations, like the ones in the stock `/etc/crontab`.
+
+use chrono::TimeZone;
+use crontab::{Entry, Run};
+use sources::Warning;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+const RUN_PARTS: &str = "run-parts";
+/// Options which take their value as the next argument.
+const OPTIONS_WITH_ARGUMENT: [&str; 4] = ["-u", "--umask", "-a", "--arg"];
+const SHELL_SEPARATORS: [char; 5] = [';', '&', '|', '(', ')'];
+
+/// One row of a listing: a run, or one of the scripts it runs.
+#[derive(Clone)]
+pub struct Row<'a, Zone: TimeZone> {
+    pub run: Run<'a, Zone>,
+    /// The script `run-parts` runs, or `None` for the entry's own command.
+    pub script: Option<PathBuf>,
+}
+
+/// The absolute directories passed to `run-parts` in `command`, in order.
+pub fn directories(command: &str) -> Vec<&str> {
+    let mut directories = vec![];
+    let mut words = command
+        .split(|character: char| character.is_whitespace() || SHELL_SEPARATORS.contains(&character))
+        .filter(|word| !word.is_empty());
+
+    while let Some(word) = words.next() {
+        if word != RUN_PARTS && !word.ends_with(&format!("/{}", RUN_PARTS)) {
+            continue;
+        }
+        while let Some(argument) = words.next() {
+            if OPTIONS_WITH_ARGUMENT.contains(&argument) {
+                words.next();
+            } else if argument.starts_with('-') && argument != "--" {
+                continue;
+            } else {
+                let directory = if argument == "--" { words.next() } else { Some(argument) };
+                directories.extend(directory.filter(|directory| directory.starts_with('/')));
+                break;
+            }
+        }
+    }
+    directories
+}
+
+/// Whether `run-parts` runs a file called `name`, which may only contain ASCII letters, digits, underscores and
+/// hyphens.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || character == '_' || character == '-')
+}
+
+/// The files `run-parts` would run in `directory`, in the order it runs them.
+pub fn scripts(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut scripts = vec![];
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        let valid_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(is_valid_name);
+        // Follows symbolic links, like `run-parts` does
+        let executable =
+            fs::metadata(&path).is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0);
+        if valid_name && executable {
+            scripts.push(path);
+        }
+    }
+    scripts.sort();
+    Ok(scripts)
+}
+
+/// Expands runs into their scripts, reading each directory once.
+pub struct Expander {
+    root: PathBuf,
+    scripts: HashMap<String, Vec<PathBuf>>,
+    /// Directories which exist but couldn't be read.
+    pub warnings: Vec<Warning>,
+}
+
+impl Expander {
+    /// Looks up `run-parts` directories below `root`, which is `/` on a real host.
+    pub fn new(root: &Path) -> Expander {
+        Expander {
+            root: root.to_path_buf(),
+            scripts: HashMap::new(),
+            warnings: vec![],
+        }
+    }
+
+    /// Every script `entry` runs through `run-parts`, in order.
+    pub fn scripts(&mut self, entry: &Entry) -> Vec<PathBuf> {
+        let mut scripts = vec![];
+        for directory in directories(&entry.command) {
+            if !self.scripts.contains_key(directory) {
+                let path = self.root.join(directory.trim_start_matches('/'));
+                let found = match self::scripts(&path) {
+                    Ok(found) => found,
+                    Err(ref error) if error.kind() == io::ErrorKind::NotFound => vec![],
+                    Err(error) => {
+                        self.warnings.push(Warning::Unreadable { path, error });
+                        vec![]
+                    }
+                };
+                self.scripts.insert(directory.to_string(), found);
+            }
+            scripts.extend(self.scripts[directory].iter().cloned());
+        }
+        scripts
+    }
+
+    /// One row per script the run executes, or the run itself if it doesn't use `run-parts` or the directories are
+    /// empty.
+    pub fn expand<'a, Zone: TimeZone>(&mut self, run: Run<'a, Zone>) -> Vec<Row<'a, Zone>> {
+        let scripts = self.scripts(run.entry);
+        if scripts.is_empty() {
+            return vec![Row { run, script: None }];
+        }
+        scripts
+            .into_iter()
+            .map(|script| Row {
+                run: run.clone(),
+                script: Some(script),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{directories, is_valid_name, scripts, Expander};
+    use chrono::{TimeZone, Utc};
+    use crontab::Crontab;
+    use test_support::fixture_root;
+
+    #[test]
+    fn should_find_run_parts_directory_in_shell_command() {
+        let command = "test -x /usr/sbin/anacron || ( cd / && run-parts --report /etc/cron.daily )";
+        assert_eq!(directories(command), vec!["/etc/cron.daily"]);
+    }
+
+    #[test]
+    fn should_find_every_run_parts_directory() {
+        let command = "/bin/run-parts /etc/cron.hourly;run-parts -u 022 -- /etc/cron.daily";
+        assert_eq!(directories(command), vec!["/etc/cron.hourly", "/etc/cron.daily"]);
+    }
+
+    #[test]
+    fn should_ignore_commands_without_run_parts() {
+        assert!(directories("echo run-parts-like /etc/cron.daily").is_empty());
+    }
+
+    #[test]
+    fn should_ignore_relative_directories() {
+        assert!(directories("cd /etc && run-parts cron.daily").is_empty());
+    }
+
+    #[test]
+    fn should_accept_run_parts_names() {
+        assert!(is_valid_name("apt-compat"));
+        assert!(is_valid_name("man_db2"));
+        assert!(!is_valid_name("backup.sh"));
+        assert!(!is_valid_name("logrotate.dpkg-old"));
+        assert!(!is_valid_name(""));
+    }
+
+    #[test]
+    fn should_list_executable_scripts_with_valid_names_in_order() {
+        let actual = scripts(&fixture_root().join("etc/cron.daily")).unwrap();
+        let names: Vec<_> = actual
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["apt-compat", "logrotate"]);
+    }
+
+    #[test]
+    fn should_expand_run_into_one_row_per_script() {
+        let crontab = Crontab::new("25 6 * * * cd / && run-parts --report /etc/cron.daily").unwrap();
+        let run = crontab
+            .next_run(&Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap())
+            .unwrap();
+        let rows = Expander::new(&fixture_root()).expand(run);
+        let scripts: Vec<_> = rows.iter().map(|row| row.script.clone().unwrap()).collect();
+        assert_eq!(
+            scripts,
+            vec![
+                fixture_root().join("etc/cron.daily/apt-compat"),
+                fixture_root().join("etc/cron.daily/logrotate"),
+            ]
+        );
+        assert!(rows.iter().all(|row| row.run.entry.line == 1));
+        assert!(rows
+            .iter()
+            .all(|row| row.run.datetime == Utc.with_ymd_and_hms(2020, 1, 1, 6, 25, 0).unwrap()));
+    }
+
+    #[test]
+    fn should_keep_run_without_scripts() {
+        let crontab = Crontab::new("17 * * * * run-parts /etc/cron.hourly").unwrap();
+        let run = crontab
+            .next_run(&Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap())
+            .unwrap();
+        let rows = Expander::new(&fixture_root()).expand(run);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].script, None);
+    }
+}
@@ -0,0 +1,313 @@
+//! Finding and reading every crontab cron would run on a host.
+
+use crontab::{Crontab, CrontabKind, Dialect, ParseError, ParseOptions};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use whoami;
+
+const SYSTEM_CRONTAB: &str = "etc/crontab";
+const SYSTEM_CRONTAB_DIRECTORY: &str = "etc/cron.d";
+/// Per-user crontabs as laid out by Vixie cron and Debian, then by cronie.
+const SPOOL_DIRECTORIES: [&str; 2] = ["var/spool/cron/crontabs", "var/spool/cron"];
+const CRONTAB_COMMAND: &str = "crontab -l";
+
+/// The text of one crontab and how to interpret it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Source {
+    /// The file the crontab was read from, or `None` if it came from a command or stream.
+    pub path: Option<PathBuf>,
+    pub name: String,
+    pub contents: String,
+    pub kind: CrontabKind,
+    /// The owner of a user crontab.
+    pub user: Option<String>,
+    pub dialect: Dialect,
+    pub host_id: Option<String>,
+}
+
+impl Source {
+    pub fn parse_options(&self) -> ParseOptions {
+        ParseOptions {
+            kind: self.kind,
+            user: self.user.clone(),
+            dialect: self.dialect,
+            host_id: self.host_id.clone(),
+        }
+    }
+}
+
+/// A problem which made cron skip a file or a line of it.
+#[derive(Debug)]
+pub enum Warning {
+    Unreadable { path: PathBuf, error: io::Error },
+    Invalid { source: String, error: ParseError },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Warning::Unreadable { ref path, ref error } => write!(formatter, "{}: {}", path.display(), error),
+            Warning::Invalid { ref source, ref error } => write!(formatter, "{}: {}", source, error),
+        }
+    }
+}
+
+impl Error for Warning {}
+
+#[derive(Debug, Default)]
+pub struct Sources {
+    pub sources: Vec<Source>,
+    /// Files and directories which exist but couldn't be read.
+    pub warnings: Vec<Warning>,
+    /// The dialect of every source added.
+    pub dialect: Dialect,
+    /// The host ID of every source added.
+    pub host_id: Option<String>,
+}
+
+impl Sources {
+    /// Reads the system crontab, `cron.d` and the spool files of every user below `root`, which is `/` on a
+    /// real host. Missing files and directories are skipped, while unreadable ones become warnings.
+    pub fn discover(root: &Path) -> Sources {
+        let mut sources = Sources::default();
+        sources.add_system_crontabs(root);
+        sources.add_spool_crontabs(root);
+        sources
+    }
+
+    /// Adds `/etc/crontab` and the files in `/etc/cron.d` below `root`.
+    pub fn add_system_crontabs(&mut self, root: &Path) {
+        self.read_file(root.join(SYSTEM_CRONTAB), CrontabKind::System, None);
+        for path in self.list_directory(&root.join(SYSTEM_CRONTAB_DIRECTORY)) {
+            self.read_file(path, CrontabKind::System, None);
+        }
+    }
+
+    /// Adds the crontab of every user in the spool directories below `root`, named after its owner.
+    pub fn add_spool_crontabs(&mut self, root: &Path) {
+        for directory in &SPOOL_DIRECTORIES {
+            for path in self.list_directory(&root.join(directory)) {
+                let user = path.file_name().map(|name| name.to_string_lossy().into_owned());
+                self.read_file(path, CrontabKind::User, user);
+            }
+        }
+    }
+
+    /// Adds the output of `crontab -l`, unless the current user's spool file has already been read.
+    pub fn add_user_crontab(&mut self) {
+        let user = whoami::username();
+        if self
+            .sources
+            .iter()
+            .any(|source| source.kind == CrontabKind::User && source.user.as_ref() == Some(&user))
+        {
+            return;
+        }
+
+        // Fails without output if the user has no crontab
+        if let Ok(output) = Command::new("crontab").arg("-l").output() {
+            if output.status.success() {
+                self.sources.push(Source {
+                    path: None,
+                    name: CRONTAB_COMMAND.to_string(),
+                    contents: String::from_utf8_lossy(&output.stdout).into_owned(),
+                    kind: CrontabKind::User,
+                    user: Some(user),
+                    dialect: self.dialect,
+                    host_id: self.host_id.clone(),
+                });
+            }
+        }
+    }
+
+    /// Parses every source into a single crontab, in the order they were read, with a warning for each line
+    /// which couldn't be parsed.
+    pub fn crontab(&self) -> (Crontab<'_>, Vec<Warning>) {
+        let mut crontab = Crontab::default();
+        let mut warnings = vec![];
+
+        for source in &self.sources {
+            let (mut parsed, errors) = Crontab::parse(&source.contents, &source.parse_options());
+            if let Some(ref path) = source.path {
+                parsed.set_source(path);
+            }
+            warnings.extend(errors.into_iter().map(|error| Warning::Invalid {
+                source: source.name.clone(),
+                error,
+            }));
+            crontab.append(parsed);
+        }
+
+        (crontab, warnings)
+    }
+
+    fn read_file(&mut self, path: PathBuf, kind: CrontabKind, user: Option<String>) {
+        // Like cron, read what can be read from files with bytes which aren't UTF-8
+        match fs::read(&path) {
+            Ok(contents) => self.sources.push(Source {
+                name: path.display().to_string(),
+                path: Some(path),
+                contents: String::from_utf8_lossy(&contents).into_owned(),
+                kind,
+                user,
+                dialect: self.dialect,
+                host_id: self.host_id.clone(),
+            }),
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => self.warnings.push(Warning::Unreadable { path, error }),
+        }
+    }
+
+    /// The crontab files in `directory` in name order, skipping subdirectories, hidden files and backups.
+    fn list_directory(&mut self, directory: &Path) -> Vec<PathBuf> {
+        let entries = match fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => return vec![],
+            Err(error) => {
+                self.warnings.push(Warning::Unreadable {
+                    path: directory.to_path_buf(),
+                    error,
+                });
+                return vec![];
+            }
+        };
+
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| {
+                let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+                !name.starts_with('.') && !name.ends_with('~') && !path.is_dir()
+            })
+            .collect();
+        paths.sort();
+        paths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Sources, Warning};
+    use crontab::CrontabKind;
+    use std::path::Path;
+    use test_support::fixture_root;
+
+    fn relative_path(path: &Path) -> String {
+        path.strip_prefix(fixture_root()).unwrap().display().to_string()
+    }
+
+    fn relative_paths(sources: &Sources) -> Vec<String> {
+        sources
+            .sources
+            .iter()
+            .filter_map(|source| source.path.as_ref())
+            .map(|path| relative_path(path))
+            .collect()
+    }
+
+    #[test]
+    fn should_read_system_and_spool_crontabs_in_order() {
+        let sources = Sources::discover(&fixture_root());
+        assert_eq!(
+            relative_paths(&sources),
+            vec![
+                "etc/crontab",
+                "etc/cron.d/backup",
+                "etc/cron.d/corrupt",
+                "etc/cron.d/logrotate",
+                "var/spool/cron/crontabs/alice",
+                "var/spool/cron/bob",
+            ]
+        );
+    }
+
+    #[test]
+    fn should_take_owner_from_spool_file_name() {
+        let sources = Sources::discover(&fixture_root());
+        let users: Vec<_> = sources
+            .sources
+            .iter()
+            .map(|source| (source.kind, source.user.as_deref()))
+            .collect();
+        assert_eq!(
+            users,
+            vec![
+                (CrontabKind::System, None),
+                (CrontabKind::System, None),
+                (CrontabKind::System, None),
+                (CrontabKind::System, None),
+                (CrontabKind::User, Some("alice")),
+                (CrontabKind::User, Some("bob")),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_warn_about_unreadable_files() {
+        let mut sources = Sources::default();
+        sources.read_file(fixture_root().join("etc/cron.d"), CrontabKind::System, None);
+        assert!(sources.sources.is_empty());
+        assert_eq!(sources.warnings.len(), 1);
+        match sources.warnings[0] {
+            Warning::Unreadable { ref path, .. } => assert!(path.ends_with("etc/cron.d")),
+            ref warning => panic!("unexpected warning {}", warning),
+        }
+    }
+
+    #[test]
+    fn should_read_files_which_are_not_utf8() {
+        let sources = Sources::discover(&fixture_root());
+        assert!(sources.warnings.is_empty());
+        assert_eq!(sources.sources[2].contents, "\u{fffd}\u{fffd} not a crontab\n");
+        let (_, warnings) = sources.crontab();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0]
+            .to_string()
+            .ends_with("etc/cron.d/corrupt: line 1, column 1: expected 5 time fields, found 4"));
+    }
+
+    #[test]
+    fn should_skip_missing_root() {
+        let sources = Sources::discover(&fixture_root().join("missing"));
+        assert!(sources.sources.is_empty());
+        assert!(sources.warnings.is_empty());
+    }
+
+    #[test]
+    fn should_tag_entries_with_file_and_line() {
+        let sources = Sources::discover(&fixture_root());
+        let (crontab, _) = sources.crontab();
+        let tags: Vec<_> = crontab
+            .entries()
+            .iter()
+            .map(|entry| (relative_path(entry.source.unwrap()), entry.line, entry.user.as_ref()))
+            .collect();
+        assert_eq!(
+            tags,
+            vec![
+                ("etc/crontab".to_string(), 4, "root"),
+                ("etc/crontab".to_string(), 5, "root"),
+                ("etc/cron.d/backup".to_string(), 3, "backup"),
+                ("etc/cron.d/logrotate".to_string(), 1, "root"),
+                ("var/spool/cron/crontabs/alice".to_string(), 2, "alice"),
+                ("var/spool/cron/bob".to_string(), 1, "bob"),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_warn_about_invalid_lines_with_source_name() {
+        let mut sources = Sources::discover(&fixture_root());
+        sources.sources[0].contents.push_str("1 2 3 4\n");
+        let (_, warnings) = sources.crontab();
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0]
+            .to_string()
+            .ends_with("etc/crontab: line 6, column 1: expected 5 time fields, found 4"));
+        assert!(warnings[1].to_string().contains("etc/cron.d/corrupt: line 1"));
+    }
+}
@@ -0,0 +1,17 @@
+//! Helpers shared by the tests of several modules.
+
+use crontab::ParseOptions;
+use std::path::{Path, PathBuf};
+
+/// Options for reading the crontab of the user `alice`, so tests don't depend on who runs them.
+pub fn user_options() -> ParseOptions {
+    ParseOptions {
+        user: Some("alice".to_string()),
+        ..ParseOptions::default()
+    }
+}
+
+/// The directory standing in for `/` in tests which read crontabs and `run-parts` directories.
+pub fn fixture_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/root")
+}